@@ -1,82 +1,240 @@
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::path::Path;
+
 use color_eyre::Result;
-use crossterm::event::KeyEvent;
-use ratatui::prelude::Rect;
-use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
-use tracing::{debug, info};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, MouseEvent, MouseEventKind};
+use ratatui::{
+    buffer::{Buffer, Cell},
+    prelude::{Position, Rect},
+    text::Span,
+    Terminal,
+};
+use tokio::{
+    sync::mpsc,
+    time::{sleep_until, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 
+#[cfg(unix)]
+use crate::session;
 use crate::{
     action::Action,
-    components::{fps::FpsCounter, home::Home, Component},
+    components::{
+        command_palette::{self, CommandPalette},
+        fps::FpsCounter,
+        help::Help,
+        home::Home,
+        jobs::Jobs,
+        which_key::WhichKey,
+        Component,
+    },
     config::Config,
-    tui::{Event, Tui},
+    external::{self, Edit},
+    history::{Change, History, Recorder},
+    keymap::{Keymap, Lookup},
+    layout::LayoutNode,
+    macros::Macros,
+    mode::{Mode, ModeStack},
+    osc::{self, Progress},
+    tasks::Tasks,
+    timers::Timers,
+    tui::{CursorShape, Event, EventSource, Tui, TuiBackend, EVENT_CAPACITY},
 };
 
+#[cfg(test)]
+mod testing;
+
 pub struct App {
     config: Config,
     tick_rate: f64,
     frame_rate: f64,
-    components: Vec<Box<dyn Component>>,
+    /// The components of the app, each with an id that names the layout slot it is drawn into.
+    components: Vec<(String, Box<dyn Component>)>,
+    layout: LayoutNode,
+    /// The area of each layout slot, recomputed whenever the terminal is resized.
+    areas: HashMap<String, Rect>,
+    /// The area each component was last drawn into, for finding the component under the mouse.
+    drawn_areas: HashMap<String, Rect>,
+    /// Whether the mouse is captured.
+    mouse: bool,
+    /// The id of the component under the mouse cursor.
+    hovered: Option<String>,
+    /// The id of the component a mouse button was pressed over, which gets the mouse events until
+    /// the button is released.
+    mouse_grab: Option<String>,
     should_quit: bool,
     should_suspend: bool,
-    mode: Mode,
-    last_tick_key_events: Vec<KeyEvent>,
+    /// Whether anything changed since the last frame was drawn.
+    needs_render: bool,
+    /// The shape of the cursor the focused component asked for in the last frame.
+    cursor_shape: CursorShape,
+    /// The window title set with `SetTitle`.
+    title: Option<String>,
+    progress: Progress,
+    /// Notifications and bells to send to the terminal before handling the next event.
+    pending_alerts: Vec<Alert>,
+    modes: ModeStack,
+    /// The id of the component that receives key and paste events.
+    focus: Option<String>,
+    /// The focus at the time each mode above the base mode was pushed, restored when it is popped.
+    focus_stack: Vec<Option<String>>,
+    /// The keybindings for the active mode and focused component.
+    keymap: Keymap,
+    /// The keys of a partially typed key sequence.
+    pending_keys: Vec<KeyEvent>,
+    /// When the pending key sequence times out.
+    pending_deadline: Option<Instant>,
+    macros: Macros,
+    history: History,
+    history_tx: mpsc::UnboundedSender<Change>,
+    history_rx: mpsc::UnboundedReceiver<Change>,
+    /// Text to edit externally before handling the next event.
+    pending_edit: Option<Edit>,
+    /// The background tasks started by components.
+    tasks: Tasks,
+    /// The timers scheduled by components, driven by the event loop of the `Tui`.
+    timers: Timers,
     action_tx: mpsc::UnboundedSender<Action>,
     action_rx: mpsc::UnboundedReceiver<Action>,
 }
 
-#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum Mode {
-    #[default]
-    Home,
-}
-
 impl App {
     pub fn new(tick_rate: f64, frame_rate: f64) -> Result<Self> {
+        // the app sends actions to itself while handling them, so they can't wait for room in a
+        // bounded queue; they are all handled before the next event is read instead, and the
+        // events that cause most of them are coalesced, see `Tui::next_event`
         let (action_tx, action_rx) = mpsc::unbounded_channel();
+        let (history_tx, history_rx) = mpsc::unbounded_channel();
+        let config = Config::new()?;
+        let tasks = Tasks::new(CancellationToken::new(), action_tx.clone());
         Ok(Self {
             tick_rate,
             frame_rate,
-            components: vec![Box::new(Home::new()), Box::new(FpsCounter::default())],
+            components: vec![
+                ("home".to_string(), Box::new(Home::new())),
+                ("fps".to_string(), Box::new(FpsCounter::default())),
+                ("which_key".to_string(), Box::new(WhichKey::default())),
+                ("jobs".to_string(), Box::new(Jobs::default())),
+                ("help".to_string(), Box::new(Help::default())),
+                (
+                    command_palette::ID.to_string(),
+                    Box::new(CommandPalette::default()),
+                ),
+            ],
+            layout: config.layout.clone().unwrap_or_default(),
+            areas: HashMap::new(),
+            drawn_areas: HashMap::new(),
+            mouse: config.mouse.unwrap_or_default(),
+            hovered: None,
+            mouse_grab: None,
             should_quit: false,
             should_suspend: false,
-            config: Config::new()?,
-            mode: Mode::Home,
-            last_tick_key_events: Vec::new(),
+            needs_render: true,
+            cursor_shape: CursorShape::Default,
+            title: None,
+            progress: Progress::Hidden,
+            pending_alerts: Vec::new(),
+            config,
+            modes: ModeStack::new(Mode::Home),
+            focus: None,
+            focus_stack: Vec::new(),
+            keymap: Keymap::default(),
+            pending_keys: Vec::new(),
+            pending_deadline: None,
+            macros: Macros::new(),
+            history: History::default(),
+            history_tx,
+            history_rx,
+            pending_edit: None,
+            tasks,
+            timers: Timers::default(),
             action_tx,
             action_rx,
         })
     }
 
+    /// Capture the mouse, overriding the config.
+    pub fn mouse(mut self, mouse: bool) -> Self {
+        self.mouse = mouse;
+        self
+    }
+
     pub async fn run(&mut self) -> Result<()> {
-        let mut tui = Tui::new()?
-            // .mouse(true) // uncomment this line to enable mouse support
+        let tui = Tui::with_viewport(self.config.viewport.unwrap_or_default())?
+            .mouse(self.mouse)
+            .paste(self.config.paste.unwrap_or(true))
+            .focus_change(true)
+            // .keyboard_enhancement(true) // uncomment this line to get key repeats and releases
             .tick_rate(self.tick_rate)
             .frame_rate(self.frame_rate);
-        tui.enter()?;
+        self.run_with(tui).await
+    }
 
-        for component in self.components.iter_mut() {
-            component.register_action_handler(self.action_tx.clone())?;
-        }
-        for component in self.components.iter_mut() {
-            component.register_config_handler(self.config.clone())?;
-        }
-        for component in self.components.iter_mut() {
-            component.init(tui.size()?)?;
-        }
+    /// Run the app without a terminal, for clients to attach to over the Unix socket at `socket`.
+    #[cfg(unix)]
+    pub async fn serve(&mut self, socket: &Path) -> Result<()> {
+        let tui = session::listen(socket)?
+            .mouse(self.mouse)
+            .tick_rate(self.tick_rate)
+            .frame_rate(self.frame_rate);
+        self.run_with(tui).await
+    }
+
+    /// Run the app on any backend and input source, e.g. a `TestBackend` with synthetic events.
+    pub async fn run_with<B: TuiBackend, I: EventSource>(
+        &mut self,
+        mut tui: Tui<B, I>,
+    ) -> Result<()> {
+        self.mouse = tui.features.mouse;
+        tui.enter()?;
+        let capabilities = tui.capabilities.unwrap_or_default();
+        self.config.capabilities = capabilities;
+        self.config.styles.fill_defaults(capabilities.background);
+        self.config.styles.downgrade(capabilities.colors);
+        // cancel the tasks along with the terminal if the app doesn't get to shut them down
+        self.tasks = Tasks::new(tui.root_token.child_token(), self.action_tx.clone());
+        self.timers = tui.timers.clone();
+        let area = tui.get_frame().area();
+        self.init(area)?;
 
         let action_tx = self.action_tx.clone();
         loop {
             self.handle_events(&mut tui).await?;
-            self.handle_actions(&mut tui)?;
+            self.handle_actions(&mut tui.terminal)?;
+            if self.needs_render || self.components.iter().any(|(_, c)| c.needs_render()) {
+                tui.request_render();
+            }
+            if tui.features.mouse != self.mouse {
+                tui.set_mouse(self.mouse)?;
+            }
+            if tui.cursor_shape != self.cursor_shape {
+                tui.set_cursor_shape(self.cursor_shape)?;
+            }
+            if tui.title != self.title {
+                tui.set_title(self.title.clone())?;
+            }
+            if tui.progress != self.progress {
+                tui.set_progress(self.progress)?;
+            }
+            for alert in self.pending_alerts.drain(..) {
+                match alert {
+                    Alert::Notify(title, body) => tui.notify(&title, &body)?,
+                    Alert::Bell => tui.bell()?,
+                }
+            }
+            if let Some(edit) = self.pending_edit.take() {
+                self.edit(&mut tui, edit).await?;
+            }
             if self.should_suspend {
-                tui.suspend()?;
+                tui.suspend().await?;
                 action_tx.send(Action::Resume)?;
                 action_tx.send(Action::ClearScreen)?;
-                // tui.mouse(true);
-                tui.enter()?;
+                tui.resume()?;
             } else if self.should_quit {
-                tui.stop()?;
+                self.tasks.shutdown().await;
+                tui.stop().await?;
                 break;
             }
         }
@@ -84,20 +242,125 @@ impl App {
         Ok(())
     }
 
-    async fn handle_events(&mut self, tui: &mut Tui) -> Result<()> {
-        let Some(event) = tui.next_event().await else {
-            return Ok(());
+    /// Leave the terminal to run the command of `edit`, then restore the terminal and send the
+    /// edited text to the component that asked for it.
+    async fn edit<B: TuiBackend, I: EventSource>(
+        &mut self,
+        tui: &mut Tui<B, I>,
+        edit: Edit,
+    ) -> Result<()> {
+        info!(
+            "Editing text for {} with `{}`",
+            edit.component, edit.command
+        );
+        tui.stop().await?;
+        tui.exit()?;
+        let result = edit.run().await;
+        tui.resume()?;
+        self.action_tx.send(Action::ClearScreen)?;
+        match result {
+            Ok(text) => self.action_tx.send(Action::Edited(edit.component, text))?,
+            Err(err) => {
+                warn!("Failed to edit text for {}: {err}", edit.component);
+                self.action_tx.send(Action::Error(err.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set up the components for drawing into `area` before handling any events.
+    fn init(&mut self, area: Rect) -> Result<()> {
+        for (_, component) in self.components.iter_mut() {
+            component.register_action_handler(self.action_tx.clone())?;
+        }
+        for (_, component) in self.components.iter_mut() {
+            component.register_config_handler(self.config.clone())?;
+        }
+        for (id, component) in self.components.iter_mut() {
+            component
+                .register_history_handler(Recorder::new(id.clone(), self.history_tx.clone()))?;
+        }
+        for (_, component) in self.components.iter_mut() {
+            component.register_task_handler(self.tasks.clone())?;
+        }
+        for (_, component) in self.components.iter_mut() {
+            component.register_timer_handler(self.timers.clone())?;
+        }
+        for (_, component) in self.components.iter_mut() {
+            component.init(area.as_size())?;
+        }
+        self.areas = self.layout.split(area);
+        let mode = self.modes.current();
+        for (_, component) in self.components.iter_mut() {
+            if let Some(action) = component.handle_mode_enter(mode, self.modes.as_slice())? {
+                self.action_tx.send(action)?;
+            }
+        }
+        self.focus_next(true)?;
+        self.rebuild_keymap()
+    }
+
+    async fn handle_events<B: TuiBackend, I: EventSource>(
+        &mut self,
+        tui: &mut Tui<B, I>,
+    ) -> Result<()> {
+        let event = match self.pending_deadline {
+            Some(deadline) => tokio::select! {
+                event = tui.next_event() => event,
+                _ = sleep_until(deadline) => return self.handle_key_sequence_timeout(),
+            },
+            None => tui.next_event().await,
         };
+        // handle the input that is already queued before drawing, so that under load a frame
+        // isn't drawn for every key. The actions of each event are handled before the next event,
+        // so that e.g. a key that changes the mode affects how the following keys are mapped.
+        let mut render = false;
+        let mut next = event;
+        let mut handled = 0;
+        while let Some(event) = next.take() {
+            match event {
+                Event::Render => render = true,
+                event => {
+                    self.handle_event(event)?;
+                    self.handle_actions(&mut tui.terminal)?;
+                }
+            }
+            handled += 1;
+            // leave the rest of the queue until the main loop has quit, suspended or edited
+            let interrupted =
+                self.should_quit || self.should_suspend || self.pending_edit.is_some();
+            if handled < EVENT_CAPACITY && !interrupted {
+                next = tui.try_next_event();
+            }
+        }
+        if render {
+            self.handle_event(Event::Render)?;
+        }
+        Ok(())
+    }
+
+    fn handle_event(&mut self, event: Event) -> Result<()> {
+        if !matches!(event, Event::Init | Event::Tick | Event::Render) {
+            self.needs_render = true;
+        }
         let action_tx = self.action_tx.clone();
         match event {
-            Event::Quit => action_tx.send(Action::Quit)?,
+            // without input the app could never be told to quit
+            Event::Quit | Event::Closed => action_tx.send(Action::Quit)?,
             Event::Tick => action_tx.send(Action::Tick)?,
             Event::Render => action_tx.send(Action::Render)?,
             Event::Resize(x, y) => action_tx.send(Action::Resize(x, y))?,
+            Event::Timer(ref action) => action_tx.send(action.clone())?,
             Event::Key(key) => self.handle_key_event(key)?,
+            Event::Mouse(mouse) => return self.handle_mouse_event(mouse),
             _ => {}
         }
-        for component in self.components.iter_mut() {
+        // key and paste events only go to the focused component, everything else goes to all
+        let focused_only = matches!(event, Event::Key(_) | Event::Paste(_));
+        for (id, component) in self.components.iter_mut() {
+            if focused_only && self.focus.as_deref() != Some(id.as_str()) {
+                continue;
+            }
             if let Some(action) = component.handle_events(Some(event.clone()))? {
                 action_tx.send(action)?;
             }
@@ -106,72 +369,877 @@ impl App {
     }
 
     fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
-        let action_tx = self.action_tx.clone();
-        let Some(keymap) = self.config.keybindings.get(&self.mode) else {
+        // releases and modifier keys pressed on their own are only reported with keyboard
+        // enhancement, and are left to the components
+        if key.kind == KeyEventKind::Release || matches!(key.code, KeyCode::Modifier(_)) {
             return Ok(());
+        }
+        // bindings match repeated keys too, whatever the state of lock keys like caps lock
+        let key = KeyEvent::new(key.code, key.modifiers);
+        if key == self.config.keymap.cancel && !self.pending_keys.is_empty() {
+            debug!("Cancelled key sequence {:?}", self.pending_keys);
+            return self.clear_pending_keys();
+        }
+        self.pending_keys.push(key);
+        match self.keymap.lookup(&self.pending_keys) {
+            Lookup::Matched(action) => {
+                info!("Got action: {action}");
+                self.action_tx.send(action)?;
+                self.clear_pending_keys()?;
+            }
+            Lookup::Pending(_) => {
+                self.pending_deadline = Some(Instant::now() + self.config.keymap.timeout());
+                self.notify_pending_keys()?;
+            }
+            Lookup::Unmatched => {
+                let prefix_len = self.pending_keys.len() - 1;
+                if prefix_len > 0 {
+                    // The key doesn't continue the sequence, so run the binding for the keys
+                    // typed so far (if any) and start a new sequence with this key.
+                    self.pending_keys.truncate(prefix_len);
+                    self.handle_key_sequence_timeout()?;
+                    return self.handle_key_event(key);
+                }
+                self.pending_keys.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a mouse event to the topmost component under the cursor, relative to the area it was
+    /// drawn into. While a button is held, the events go to the component it was pressed over, so
+    /// that dragging can leave the component.
+    fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Result<()> {
+        let position = Position::new(mouse.column, mouse.row);
+        // components drawn later are drawn on top
+        let hit = self
+            .components
+            .iter()
+            .rev()
+            .find(|(id, component)| {
+                self.drawn_areas
+                    .get(id)
+                    .is_some_and(|area| component.hit_test(*area, position))
+            })
+            .map(|(id, _)| id.clone());
+        self.set_hovered(hit.clone())?;
+        let target = match mouse.kind {
+            MouseEventKind::Down(_) => {
+                self.mouse_grab = hit.clone();
+                hit
+            }
+            MouseEventKind::Drag(_) => self.mouse_grab.clone().or(hit),
+            MouseEventKind::Up(_) => self.mouse_grab.take().or(hit),
+            _ => hit,
+        };
+        let Some(target) = target else {
+            return Ok(());
+        };
+        let area = self.drawn_areas.get(&target).copied().unwrap_or_default();
+        let mouse = MouseEvent {
+            column: mouse.column.saturating_sub(area.x),
+            row: mouse.row.saturating_sub(area.y),
+            ..mouse
         };
-        match keymap.get(&vec![key]) {
-            Some(action) => {
-                info!("Got action: {action:?}");
-                action_tx.send(action.clone())?;
+        let component = self.components.iter_mut().find(|(id, _)| *id == target);
+        if let Some((_, component)) = component {
+            if let Some(action) = component.handle_events(Some(Event::Mouse(mouse)))? {
+                self.action_tx.send(action)?;
             }
-            _ => {
-                // If the key was not handled as a single key action,
-                // then consider it for multi-key combinations.
-                self.last_tick_key_events.push(key);
+        }
+        Ok(())
+    }
 
-                // Check for multi-key combinations
-                if let Some(action) = keymap.get(&self.last_tick_key_events) {
-                    info!("Got action: {action:?}");
-                    action_tx.send(action.clone())?;
+    /// Tell the components when the mouse cursor moves off or onto them.
+    fn set_hovered(&mut self, hovered: Option<String>) -> Result<()> {
+        if hovered == self.hovered {
+            return Ok(());
+        }
+        let previous = std::mem::replace(&mut self.hovered, hovered.clone());
+        for (id, component) in self.components.iter_mut() {
+            if previous.as_ref() == Some(id) {
+                if let Some(action) = component.handle_mouse_leave()? {
+                    self.action_tx.send(action)?;
+                }
+            }
+        }
+        for (id, component) in self.components.iter_mut() {
+            if hovered.as_ref() == Some(id) {
+                if let Some(action) = component.handle_mouse_enter()? {
+                    self.action_tx.send(action)?;
                 }
             }
         }
         Ok(())
     }
 
-    fn handle_actions(&mut self, tui: &mut Tui) -> Result<()> {
+    /// Run the action bound to the pending keys, if any, once no more keys arrive in time to
+    /// complete a longer sequence.
+    fn handle_key_sequence_timeout(&mut self) -> Result<()> {
+        if let Lookup::Pending(Some(action)) = self.keymap.lookup(&self.pending_keys) {
+            info!("Got action: {action}");
+            self.action_tx.send(action)?;
+        }
+        self.clear_pending_keys()
+    }
+
+    fn clear_pending_keys(&mut self) -> Result<()> {
+        self.pending_keys.clear();
+        if self.pending_deadline.take().is_some() {
+            self.notify_pending_keys()?;
+        }
+        Ok(())
+    }
+
+    /// Tell components about the pending keys and the bindings they could still complete.
+    fn notify_pending_keys(&mut self) -> Result<()> {
+        let continuations = if self.pending_keys.is_empty() {
+            Vec::new()
+        } else {
+            self.keymap.continuations(&self.pending_keys)
+        };
+        for (_, component) in self.components.iter_mut() {
+            if let Some(action) =
+                component.handle_pending_keys(&self.pending_keys, &continuations)?
+            {
+                self.action_tx.send(action)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuild the keymap after the active mode or the focused component changes.
+    fn rebuild_keymap(&mut self) -> Result<()> {
+        self.keymap = self
+            .config
+            .keymap(self.modes.current(), self.focus.as_deref());
+        // a key sequence started with the old keymap can't be completed with the new one
+        self.clear_pending_keys()
+    }
+
+    /// Handle every queued action, drawing a frame once they are all handled if any of them was
+    /// `Render`.
+    fn handle_actions<B: TuiBackend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let mut render = false;
         while let Ok(action) = self.action_rx.try_recv() {
             if action != Action::Tick && action != Action::Render {
-                debug!("{action:?}");
+                debug!("{action}");
+                // any other action may change what is drawn
+                self.needs_render = true;
+                // a different kind of action than the last one ends the group of changes
+                self.record_changes();
+                self.history.handling(&action);
             }
+            self.macros.record(&action);
             match action {
-                Action::Tick => {
-                    self.last_tick_key_events.drain(..);
-                }
                 Action::Quit => self.should_quit = true,
                 Action::Suspend => self.should_suspend = true,
                 Action::Resume => self.should_suspend = false,
-                Action::ClearScreen => tui.terminal.clear()?,
-                Action::Resize(w, h) => self.handle_resize(tui, w, h)?,
-                Action::Render => self.render(tui)?,
+                Action::ClearScreen => terminal.clear()?,
+                Action::Resize(..) => self.handle_resize(terminal)?,
+                Action::Render => render = true,
+                Action::SwitchMode(mode) => {
+                    let previous = self.modes.switch(mode);
+                    self.handle_mode_change(previous)?;
+                }
+                Action::PushMode(mode) => {
+                    let previous = self.modes.current();
+                    self.modes.push(mode);
+                    self.focus_stack.push(self.focus.clone());
+                    self.handle_mode_change(previous)?;
+                }
+                Action::PopMode => {
+                    if let Some(previous) = self.modes.pop() {
+                        if let Some(focus) = self.focus_stack.pop() {
+                            self.set_focus(focus)?;
+                        }
+                        self.handle_mode_change(previous)?;
+                    }
+                }
+                Action::FocusNext => self.focus_next(true)?,
+                Action::FocusPrevious => self.focus_next(false)?,
+                Action::Focus(ref id) => self.set_focus(Some(id.clone()))?,
+                Action::RecordMacro(ref register) => {
+                    if let Err(err) = self.macros.start(register) {
+                        self.macro_failed(err)?;
+                    }
+                }
+                Action::StopRecording => {
+                    if let Err(err) = self.macros.stop() {
+                        self.macro_failed(err)?;
+                    }
+                }
+                Action::PlayMacro(ref register, count) => self.play_macro(register, count)?,
+                Action::Undo => self.undo()?,
+                Action::Redo => self.redo()?,
+                Action::ToggleMouse => {
+                    self.mouse = !self.mouse;
+                    if !self.mouse {
+                        self.mouse_grab = None;
+                        self.set_hovered(None)?;
+                    }
+                }
+                Action::SetTitle(ref title) => self.title = Some(title.clone()),
+                Action::RestoreTitle => self.title = None,
+                Action::Progress(progress) => self.progress = progress,
+                Action::Notify(ref title, ref body) => self
+                    .pending_alerts
+                    .push(Alert::Notify(title.clone(), body.clone())),
+                Action::Bell => self.pending_alerts.push(Alert::Bell),
+                Action::CancelTask(id) if !self.tasks.cancel(id) => {
+                    debug!("Task {id} is not running")
+                }
+                Action::CancelTasks => self.tasks.cancel_all(),
+                Action::Edit(ref component, ref text) => {
+                    self.pending_edit = Some(Edit {
+                        component: component.clone(),
+                        command: external::editor(),
+                        text: text.clone(),
+                    })
+                }
+                Action::EditWith(ref component, ref command, ref text) => {
+                    self.pending_edit = Some(Edit {
+                        component: component.clone(),
+                        command: command.clone(),
+                        text: text.clone(),
+                    })
+                }
                 _ => {}
             }
-            for component in self.components.iter_mut() {
+            for (_, component) in self.components.iter_mut() {
                 if let Some(action) = component.update(action.clone())? {
                     self.action_tx.send(action)?
                 };
             }
+            self.record_changes();
+        }
+        if render {
+            self.render(terminal)?;
+        }
+        Ok(())
+    }
+
+    /// Add the changes components recorded since the last call to the undo history.
+    fn record_changes(&mut self) {
+        while let Ok(change) = self.history_rx.try_recv() {
+            self.history.push(change);
+        }
+    }
+
+    /// Drop the changes the components recorded while undoing or redoing, which would otherwise
+    /// clear the redo stack.
+    fn discard_changes(&mut self) {
+        while self.history_rx.try_recv().is_ok() {}
+    }
+
+    /// Ask the components that made the most recent group of changes to undo them.
+    fn undo(&mut self) -> Result<()> {
+        self.record_changes();
+        let Some(changes) = self.history.undo() else {
+            debug!("Nothing to undo");
+            return Ok(());
+        };
+        for change in changes {
+            let component = self
+                .components
+                .iter_mut()
+                .find(|(id, _)| id == change.component());
+            if let Some((_, component)) = component {
+                if let Some(action) = component.undo(change)? {
+                    self.action_tx.send(action)?;
+                }
+            }
+        }
+        self.discard_changes();
+        Ok(())
+    }
+
+    /// Ask the components that made the most recently undone group of changes to redo them.
+    fn redo(&mut self) -> Result<()> {
+        self.record_changes();
+        let Some(changes) = self.history.redo() else {
+            debug!("Nothing to redo");
+            return Ok(());
+        };
+        for change in changes {
+            let component = self
+                .components
+                .iter_mut()
+                .find(|(id, _)| id == change.component());
+            if let Some((_, component)) = component {
+                if let Some(action) = component.redo(change)? {
+                    self.action_tx.send(action)?;
+                }
+            }
         }
+        self.discard_changes();
         Ok(())
     }
 
-    fn handle_resize(&mut self, tui: &mut Tui, w: u16, h: u16) -> Result<()> {
-        tui.resize(Rect::new(0, 0, w, h))?;
-        self.render(tui)?;
+    /// Report that the recorded macros couldn't be saved, keeping the app running.
+    fn macro_failed(&self, err: color_eyre::Report) -> Result<()> {
+        warn!("Failed to save macros: {err}");
+        self.action_tx
+            .send(Action::Error(format!("Failed to save macros: {err}")))?;
         Ok(())
     }
 
-    fn render(&mut self, tui: &mut Tui) -> Result<()> {
-        tui.draw(|frame| {
-            for component in self.components.iter_mut() {
-                if let Err(err) = component.draw(frame, frame.area()) {
+    /// Queue the actions of the macro in `register` to run `count` times.
+    fn play_macro(&mut self, register: &str, count: u16) -> Result<()> {
+        let Some(actions) = self.macros.get(register) else {
+            warn!("No macro recorded in register {register:?}");
+            return Ok(());
+        };
+        for _ in 0..count {
+            for action in actions {
+                self.action_tx.send(action.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Notify components that the active mode changed from `previous` to the top of the stack.
+    fn handle_mode_change(&mut self, previous: Mode) -> Result<()> {
+        let current = self.modes.current();
+        if previous == current {
+            return Ok(());
+        }
+        info!("Mode changed from {previous:?} to {current:?}");
+        self.rebuild_keymap()?;
+        for (_, component) in self.components.iter_mut() {
+            if let Some(action) = component.handle_mode_exit(previous, self.modes.as_slice())? {
+                self.action_tx.send(action)?;
+            }
+        }
+        for (_, component) in self.components.iter_mut() {
+            if let Some(action) = component.handle_mode_enter(current, self.modes.as_slice())? {
+                self.action_tx.send(action)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move focus to the next (or previous) focusable component, wrapping around at the ends.
+    fn focus_next(&mut self, forward: bool) -> Result<()> {
+        let focusable: Vec<&String> = self
+            .components
+            .iter()
+            .filter(|(_, component)| component.focusable())
+            .map(|(id, _)| id)
+            .collect();
+        if focusable.is_empty() {
+            return Ok(());
+        }
+        let len = focusable.len();
+        let next = match focusable
+            .iter()
+            .position(|id| self.focus.as_ref() == Some(*id))
+        {
+            Some(index) if forward => (index + 1) % len,
+            Some(index) => (index + len - 1) % len,
+            None => 0,
+        };
+        let id = focusable[next].clone();
+        self.set_focus(Some(id))
+    }
+
+    /// Give focus to the component with the given id, notifying the components that lost and
+    /// gained focus.
+    fn set_focus(&mut self, id: Option<String>) -> Result<()> {
+        if self.focus == id {
+            return Ok(());
+        }
+        if let Some(id) = &id {
+            if !self.components.iter().any(|(other, _)| other == id) {
+                warn!("Cannot focus unknown component {id:?}");
+                return Ok(());
+            }
+        }
+        let previous = std::mem::replace(&mut self.focus, id);
+        debug!("Focus changed from {previous:?} to {:?}", self.focus);
+        self.rebuild_keymap()?;
+        for (id, component) in self.components.iter_mut() {
+            let action = if previous.as_deref() == Some(id.as_str()) {
+                component.handle_focus_lost()?
+            } else if self.focus.as_deref() == Some(id.as_str()) {
+                component.handle_focus_gained()?
+            } else {
+                None
+            };
+            if let Some(action) = action {
+                self.action_tx.send(action)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_resize<B: TuiBackend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        // the terminal works out the new size of the viewport, which is only part of the screen
+        // when the app is drawn inline
+        terminal.autoresize()?;
+        let frame_area = terminal.get_frame().area();
+        self.areas = self.layout.split(frame_area);
+        for (id, component) in self.components.iter_mut() {
+            let area = self.areas.get(id).copied().unwrap_or(frame_area);
+            if let Some(action) = component.handle_resize(area)? {
+                self.action_tx.send(action)?;
+            }
+        }
+        self.render(terminal)?;
+        Ok(())
+    }
+
+    fn render<B: TuiBackend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        self.needs_render = false;
+        let mut cursor = None;
+        let mut hyperlinks = Vec::new();
+        let completed = terminal.draw(|frame| {
+            for (id, component) in self.components.iter_mut() {
+                // components without a slot in the layout are drawn over the whole frame
+                let area = self.areas.get(id).copied().unwrap_or(frame.area());
+                self.drawn_areas.insert(id.clone(), area);
+                if let Err(err) = component.draw(frame, area) {
                     let _ = self
                         .action_tx
                         .send(Action::Error(format!("Failed to draw: {:?}", err)));
                 }
+                if self.focus.as_ref() == Some(id) {
+                    cursor = component.cursor();
+                }
+                hyperlinks.extend(component.hyperlinks());
+            }
+            // the terminal shows the cursor where the frame asks for it and hides it otherwise
+            if let Some(cursor) = cursor {
+                frame.set_cursor_position(cursor.position);
             }
         })?;
+        self.cursor_shape = cursor.map(|cursor| cursor.shape).unwrap_or_default();
+        let area = completed.area;
+        let links: Vec<_> = if self.config.capabilities.integrations.hyperlinks {
+            hyperlinks
+                .into_iter()
+                .map(|link| (link_cells(completed.buffer, link.area), link.url))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        terminal.backend_mut().set_viewport_area(area);
+        if links.is_empty() {
+            return Ok(());
+        }
+        // ratatui would count the escape sequences towards the width of a cell, so the text of
+        // each link is drawn again, wrapped in OSC 8, after the frame is drawn
+        let backend = terminal.backend_mut();
+        for (cells, url) in links {
+            backend.write_escape(&osc::hyperlink(&url))?;
+            backend.draw(cells.iter().map(|(x, y, cell)| (*x, *y, cell)))?;
+            backend.write_escape(osc::HYPERLINK_END)?;
+        }
+        backend.flush()?;
+        if let Some(cursor) = cursor {
+            terminal.set_cursor_position(cursor.position)?;
+        }
+        Ok(())
+    }
+}
+
+/// A notification or bell waiting to be sent to the terminal.
+enum Alert {
+    Notify(String, String),
+    Bell,
+}
+
+/// The cells of `buffer` in `area`, leaving out the cells covered by wide characters.
+fn link_cells(buffer: &Buffer, area: Rect) -> Vec<(u16, u16, Cell)> {
+    let area = area.intersection(buffer.area);
+    let mut cells = Vec::new();
+    for y in area.top()..area.bottom() {
+        let mut covered = 0;
+        for x in area.left()..area.right() {
+            let cell = &buffer[(x, y)];
+            if covered > 0 {
+                covered -= 1;
+                continue;
+            }
+            covered = Span::raw(cell.symbol()).width().saturating_sub(1);
+            cells.push((x, y, cell.clone()));
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crossterm::event::{KeyEventState, KeyModifiers, ModifierKeyCode, MouseButton};
+    use pretty_assertions::assert_eq;
+    use ratatui::{backend::TestBackend, Frame};
+    use tokio::time::timeout;
+
+    use super::{
+        testing::{ActionLog, TestApp},
+        *,
+    };
+    use crate::{components::popup_area, tui::ViewportMode};
+
+    #[tokio::test]
+    async fn test_draws_components_into_their_slots() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.render()?;
+        let screen = app.screen();
+        let lines: Vec<&str> = screen.lines().collect();
+        assert_eq!(lines[1], "hello world");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quit_key() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        assert!(!app.should_quit());
+        app.keys("<q>")?;
+        assert!(app.should_quit());
+        assert!(app.actions().contains(&Action::Quit));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_help_overlay() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.keys("<?>")?;
+        app.render()?;
+        assert!(app.screen().contains("Help: Home mode"));
+        assert!(app.actions().contains(&Action::PushMode(Mode::Help)));
+        app.keys("<esc>")?;
+        app.render()?;
+        assert!(!app.screen().contains("Help: Home mode"));
+        assert!(!app.should_quit());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_palette_runs_chosen_action() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.keys("<ctrl-p>")?;
+        // the palette gets the keys, so typing doesn't trigger the bindings of the Home mode
+        app.type_text("q")?;
+        assert!(!app.should_quit());
+        app.keys("<backspace>")?;
+        app.type_text("clear")?;
+        app.keys("<enter>")?;
+        assert_eq!(app.actions().last(), Some(&Action::ClearScreen));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cursor_is_shown_for_focused_component() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.render()?;
+        assert_eq!(app.app.cursor_shape, CursorShape::Default);
+        app.keys("<ctrl-p>")?;
+        app.type_text("qu")?;
+        app.render()?;
+        // at the end of "> qu", inside the border of the palette
+        let palette = popup_area(Rect::new(0, 0, 60, 20), 60, 60);
+        assert_eq!(
+            app.terminal.get_cursor_position()?,
+            Position::new(palette.x + 5, palette.y + 1)
+        );
+        assert_eq!(app.app.cursor_shape, CursorShape::BlinkingBar);
+        app.keys("<esc>")?;
+        app.render()?;
+        assert_eq!(app.app.cursor_shape, CursorShape::Default);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_terminal_integration_actions() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.app
+            .action_tx
+            .send(Action::SetTitle("Build".to_string()))?;
+        app.app
+            .action_tx
+            .send(Action::Progress(Progress::Normal(40)))?;
+        app.app
+            .action_tx
+            .send(Action::Notify("Build".to_string(), "done".to_string()))?;
+        app.render()?;
+        assert_eq!(app.app.title.as_deref(), Some("Build"));
+        assert_eq!(app.app.progress, Progress::Normal(40));
+        assert!(matches!(app.app.pending_alerts[..], [Alert::Notify(..)]));
+        app.app.action_tx.send(Action::RestoreTitle)?;
+        app.render()?;
+        assert_eq!(app.app.title, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_link_cells_skip_wide_characters() {
+        let buffer = Buffer::with_lines(["a界b"]);
+        let cells = link_cells(&buffer, Rect::new(0, 0, 10, 1));
+        let symbols: Vec<_> = cells
+            .iter()
+            .map(|(x, _, cell)| (*x, cell.symbol()))
+            .collect();
+        assert_eq!(symbols, vec![(0, "a"), (1, "界"), (3, "b")]);
+    }
+
+    #[tokio::test]
+    async fn test_paste_goes_to_focused_component() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        // pasted text doesn't trigger keybindings
+        app.event(Event::Paste("q".to_string()))?;
+        assert!(!app.should_quit());
+        app.keys("<ctrl-p>")?;
+        app.event(Event::Paste("clear\n".to_string()))?;
+        app.keys("<enter>")?;
+        assert_eq!(app.actions().last(), Some(&Action::ClearScreen));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_key_sequence_timeout() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.keys("<m>")?;
+        app.render()?;
+        assert!(app.screen().contains("StopRecording"));
+        app.timeout()?;
+        app.render()?;
+        assert!(!app.screen().contains("StopRecording"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_enhanced_key_events() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        let key = |code, kind| {
+            Event::Key(KeyEvent {
+                code,
+                modifiers: KeyModifiers::NONE,
+                kind,
+                state: KeyEventState::CAPS_LOCK,
+            })
+        };
+        app.events([
+            key(KeyCode::Char('m'), KeyEventKind::Press),
+            key(KeyCode::Char('m'), KeyEventKind::Release),
+            key(
+                KeyCode::Modifier(ModifierKeyCode::LeftShift),
+                KeyEventKind::Press,
+            ),
+            // the key is held down
+            key(KeyCode::Char('m'), KeyEventKind::Repeat),
+        ])?;
+        assert_eq!(app.actions().last(), Some(&Action::StopRecording));
+        app.event(key(KeyCode::Char('q'), KeyEventKind::Release))?;
+        assert!(!app.should_quit());
+        Ok(())
+    }
+
+    /// Records the mouse events it gets and when the mouse enters and leaves it.
+    #[derive(Default)]
+    struct MouseRecorder(Arc<Mutex<Vec<String>>>);
+
+    impl Component for MouseRecorder {
+        fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Result<Option<Action>> {
+            let event = format!("{:?} {},{}", mouse.kind, mouse.column, mouse.row);
+            self.0.lock().unwrap().push(event);
+            Ok(None)
+        }
+
+        fn handle_mouse_enter(&mut self) -> Result<Option<Action>> {
+            self.0.lock().unwrap().push("enter".to_string());
+            Ok(None)
+        }
+
+        fn handle_mouse_leave(&mut self) -> Result<Option<Action>> {
+            self.0.lock().unwrap().push("leave".to_string());
+            Ok(None)
+        }
+
+        fn draw(&mut self, _frame: &mut Frame, _area: Rect) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mouse_events_go_to_component_under_cursor() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        let recorder = MouseRecorder::default();
+        let events = recorder.0.clone();
+        app.app.components[0] = ("home".to_string(), Box::new(recorder));
+        app.render()?;
+        let mouse = |kind, column, row| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let left = MouseButton::Left;
+        app.events([
+            // the fps counter is drawn into the first row, above the home component
+            mouse(MouseEventKind::Moved, 5, 0),
+            mouse(MouseEventKind::Moved, 5, 3),
+            mouse(MouseEventKind::Down(left), 5, 3),
+            // dragging off the component keeps sending it the events
+            mouse(MouseEventKind::Drag(left), 5, 0),
+            mouse(MouseEventKind::Up(left), 5, 0),
+            mouse(MouseEventKind::Moved, 6, 0),
+        ])?;
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "enter",
+                "Moved 5,2",
+                "Down(Left) 5,2",
+                "leave",
+                "Drag(Left) 5,0",
+                "Up(Left) 5,0",
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_toggle_mouse() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        assert!(!app.app.mouse);
+        app.app.action_tx.send(Action::ToggleMouse)?;
+        app.event(Event::Tick)?;
+        assert!(app.app.mouse);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timer_event_sends_its_action() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.event(Event::Timer(Action::ToggleMouse))?;
+        assert!(app.actions().contains(&Action::ToggleMouse));
+        assert!(app.app.mouse);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_task() -> Result<()> {
+        let mut app = TestApp::new(60, 8)?;
+        let (_keep_open, never) = tokio::sync::oneshot::channel::<()>();
+        let id = app.app.tasks.spawn("Download", |_| async move {
+            let _ = never.await;
+            Ok(None)
+        });
+        app.render()?;
+        assert!(app.screen().contains("Download"));
+        app.app.action_tx.send(Action::CancelTask(id))?;
+        app.event(Event::Tick)?;
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while !app.app.tasks.running().is_empty() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await?;
+        app.event(Event::Tick)?;
+        assert!(app.actions().contains(&Action::TaskCancelled(id)));
+        app.render()?;
+        assert!(!app.screen().contains("Download"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_with_synthetic_events() -> Result<()> {
+        let events = vec![
+            Event::Key(KeyEvent::from(KeyCode::Char('?'))),
+            Event::Key(KeyEvent::from(KeyCode::Char('q'))),
+            Event::Key(KeyEvent::from(KeyCode::Char('q'))),
+        ];
+        let tui = Tui::with_backend(TestBackend::new(60, 20), events, ViewportMode::Fullscreen)?;
+        let mut app = App::new(4.0, 60.0)?;
+        let log = ActionLog::default();
+        log.attach(&mut app);
+        app.run_with(tui).await?;
+        assert!(app.should_quit);
+        // the first `q` closes the help, the second quits
+        let actions = log.actions();
+        assert!(actions.contains(&Action::Help));
+        assert_eq!(actions.last(), Some(&Action::Quit));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_with_quits_when_the_events_run_out() -> Result<()> {
+        let events = vec![Event::Key(KeyEvent::from(KeyCode::Char('?')))];
+        let tui = Tui::with_backend(TestBackend::new(60, 20), events, ViewportMode::Fullscreen)?;
+        let mut app = App::new(4.0, 60.0)?;
+        timeout(Duration::from_secs(1), app.run_with(tui)).await??;
+        assert!(app.should_quit);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_edit_waits_for_the_main_loop() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.app.action_tx.send(Action::EditWith(
+            "home".to_string(),
+            "nano".to_string(),
+            "hello".to_string(),
+        ))?;
+        app.event(Event::Tick)?;
+        let edit = Edit {
+            component: "home".to_string(),
+            command: "nano".to_string(),
+            text: "hello".to_string(),
+        };
+        assert_eq!(app.app.pending_edit, Some(edit));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_edit_sends_edited_text() -> Result<()> {
+        let tui = Tui::with_backend(TestBackend::new(60, 20), vec![], ViewportMode::Fullscreen)?;
+        let mut app = App::new(4.0, 60.0)?;
+        app.action_tx.send(Action::EditWith(
+            "home".to_string(),
+            "printf ' world' >>".to_string(),
+            "hello".to_string(),
+        ))?;
+        app.action_tx.send(Action::Quit)?;
+        app.run_with(tui).await?;
+        // the edit runs before the app quits, so the edited text is never handled
+        assert_eq!(app.action_rx.try_recv().ok(), Some(Action::ClearScreen));
+        assert_eq!(
+            app.action_rx.try_recv().ok(),
+            Some(Action::Edited(
+                "home".to_string(),
+                "hello world".to_string()
+            ))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_render_only_when_needed() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.render()?;
+        assert!(!app.app.needs_render);
+        app.event(Event::Tick)?;
+        assert!(!app.app.needs_render);
+        app.keys("<tab>")?;
+        assert!(app.app.needs_render);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resize() -> Result<()> {
+        let mut app = TestApp::new(60, 20)?;
+        app.event(Event::Resize(30, 5))?;
+        assert_eq!(app.render()?.area, Rect::new(0, 0, 30, 5));
+        assert_eq!(app.app.areas.get("home"), Some(&Rect::new(0, 1, 30, 4)));
         Ok(())
     }
 }
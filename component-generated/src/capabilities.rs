@@ -0,0 +1,465 @@
+#![allow(dead_code)] // Remove this once you start using the code
+
+use std::{env, io::Write, time::Duration};
+
+use color_eyre::Result;
+use ratatui::style::{Color, Style};
+use tracing::debug;
+
+/// How long to wait for the terminal to report its background colour.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// The colours a terminal can show, from fewest to most.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    /// No colours, e.g. because `NO_COLOR` is set or the terminal is `dumb`.
+    None,
+    /// The 16 ANSI colours, whose exact shades depend on the terminal's theme.
+    Ansi16,
+    /// The 256 colour palette.
+    Ansi256,
+    /// Any RGB colour.
+    #[default]
+    TrueColor,
+}
+
+/// Whether the terminal has a light or a dark background.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Background {
+    Light,
+    Dark,
+}
+
+/// How a terminal shows desktop notifications.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Notifications {
+    /// OSC 9, as in iTerm2, kitty, WezTerm and Ghostty.
+    Osc9,
+    /// OSC 777, as in foot, urxvt and some VTE based terminals.
+    Osc777,
+}
+
+/// The terminal integrations beyond drawing that the terminal supports, see [`crate::osc`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Integrations {
+    /// Whether the window title can be set.
+    pub title: bool,
+    /// Whether OSC 8 hyperlinks can be clicked.
+    pub hyperlinks: bool,
+    /// Whether OSC 9;4 progress is shown.
+    pub progress: bool,
+    pub notifications: Option<Notifications>,
+    /// Whether the app runs inside tmux, which only passes progress and notifications on to the
+    /// terminal when they are wrapped for passthrough.
+    pub tmux: bool,
+}
+
+/// What the terminal supports, detected when a [`crate::tui::Tui`] is first entered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub colors: ColorSupport,
+    /// The background of the terminal, if it reported its background colour.
+    pub background: Option<Background>,
+    pub integrations: Integrations,
+}
+
+impl Capabilities {
+    /// Detect the colours the terminal supports from the environment, and ask the terminal for its
+    /// background colour. The terminal must be in raw mode, and nothing else may be reading its
+    /// input.
+    pub fn detect(writer: &mut impl Write) -> Result<Self> {
+        let colors = ColorSupport::detect();
+        let background = match colors {
+            ColorSupport::None => None,
+            _ => query_background(writer)?,
+        };
+        let integrations = Integrations::detect();
+        debug!("Detected {colors:?} colors, a {background:?} background and {integrations:?}");
+        Ok(Self {
+            colors,
+            background,
+            integrations,
+        })
+    }
+}
+
+impl Integrations {
+    /// Detect the integrations the terminal supports from the variables terminals set in the
+    /// environment. Inside tmux, `TERM` and `TERM_PROGRAM` describe tmux, but the variables set
+    /// by the outer terminal are still inherited.
+    pub fn detect() -> Self {
+        Self::from_env(|name| env::var(name).ok())
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let set = |name: &str| var(name).is_some_and(|value| !value.is_empty());
+        let term = var("TERM").unwrap_or_default();
+        let term_program = var("TERM_PROGRAM").unwrap_or_default();
+
+        let kitty = set("KITTY_WINDOW_ID") || term == "xterm-kitty";
+        let wezterm = set("WEZTERM_EXECUTABLE") || term_program == "WezTerm";
+        let ghostty = set("GHOSTTY_RESOURCES_DIR") || term == "xterm-ghostty";
+        let iterm = set("ITERM_SESSION_ID") || term_program == "iTerm.app";
+        let windows_terminal = set("WT_SESSION");
+        let conemu = set("ConEmuPID");
+        let foot = term.starts_with("foot");
+        let rxvt = term.starts_with("rxvt");
+        let vte = var("VTE_VERSION")
+            .and_then(|version| version.parse::<u32>().ok())
+            .is_some_and(|version| version >= 5000);
+        let konsole = set("KONSOLE_VERSION");
+        let vscode = term_program == "vscode";
+        let alacritty = set("ALACRITTY_WINDOW_ID") || term == "alacritty";
+
+        let notifications = if kitty || wezterm || ghostty || iterm {
+            Some(Notifications::Osc9)
+        } else if foot || rxvt || vte {
+            Some(Notifications::Osc777)
+        } else {
+            None
+        };
+        Self {
+            title: term != "dumb" && (!term.is_empty() || cfg!(windows)),
+            hyperlinks: kitty
+                || wezterm
+                || ghostty
+                || iterm
+                || windows_terminal
+                || foot
+                || vte
+                || konsole
+                || vscode
+                || alacritty,
+            progress: windows_terminal || conemu || ghostty || iterm,
+            notifications,
+            tmux: set("TMUX"),
+        }
+    }
+}
+
+impl ColorSupport {
+    /// Detect the colours the terminal supports from `NO_COLOR`, `COLORTERM` and `TERM`.
+    pub fn detect() -> Self {
+        Self::from_env(
+            env::var("NO_COLOR").ok().as_deref(),
+            env::var("COLORTERM").ok().as_deref(),
+            env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn from_env(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> Self {
+        // see https://no-color.org
+        if no_color.is_some_and(|value| !value.is_empty()) {
+            return ColorSupport::None;
+        }
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return ColorSupport::TrueColor;
+        }
+        match term {
+            Some("dumb") => ColorSupport::None,
+            Some(term) if term.ends_with("-direct") => ColorSupport::TrueColor,
+            Some(term) if term.contains("256color") => ColorSupport::Ansi256,
+            Some(_) => ColorSupport::Ansi16,
+            // the Windows console supports RGB colours but doesn't set `TERM`
+            None if cfg!(windows) => ColorSupport::TrueColor,
+            None => ColorSupport::None,
+        }
+    }
+
+    /// The closest colour to `color` that the terminal can show, or `None` if it shows no colours.
+    pub fn downgrade(self, color: Color) -> Option<Color> {
+        match (self, color) {
+            (ColorSupport::None, _) => None,
+            (ColorSupport::TrueColor, color) => Some(color),
+            (ColorSupport::Ansi256, Color::Rgb(r, g, b)) => {
+                Some(Color::Indexed(rgb_to_256(r, g, b)))
+            }
+            (ColorSupport::Ansi256, color) => Some(color),
+            (ColorSupport::Ansi16, Color::Rgb(r, g, b)) => Some(rgb_to_16(r, g, b)),
+            (ColorSupport::Ansi16, Color::Indexed(index)) => {
+                let (r, g, b) = indexed_to_rgb(index);
+                Some(rgb_to_16(r, g, b))
+            }
+            (ColorSupport::Ansi16, color) => Some(color),
+        }
+    }
+
+    /// `style` with its colours downgraded to those the terminal can show.
+    pub fn downgrade_style(self, mut style: Style) -> Style {
+        style.fg = style.fg.and_then(|color| self.downgrade(color));
+        style.bg = style.bg.and_then(|color| self.downgrade(color));
+        style
+    }
+}
+
+/// The 16 ANSI colours, with the shades xterm uses for them.
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of each channel in the 6x6x6 colour cube of the 256 colour palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let channel = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2) as u32;
+    channel(r1, r2) + channel(g1, g2) + channel(b1, b2)
+}
+
+/// The RGB value of a colour in the 256 colour palette.
+fn indexed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_16[index as usize].1,
+        16..=231 => {
+            let index = index - 16;
+            let level = |n: u8| CUBE_LEVELS[n as usize];
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        232..=255 => {
+            let gray = 8 + 10 * (index - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// The closest colour in the colour cube or the grayscale ramp of the 256 colour palette, whose
+/// shades are the same in every terminal, unlike the first 16 colours.
+fn rgb_to_256(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&n| CUBE_LEVELS[n].abs_diff(value))
+            .unwrap_or_default() as u8
+    };
+    let cube = 16 + 36 * nearest_level(r) + 6 * nearest_level(g) + nearest_level(b);
+    let average = ((u16::from(r) + u16::from(g) + u16::from(b)) / 3) as u8;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23);
+    [cube, gray]
+        .into_iter()
+        .min_by_key(|&index| distance((r, g, b), indexed_to_rgb(index)))
+        .unwrap_or(cube)
+}
+
+fn rgb_to_16(r: u8, g: u8, b: u8) -> Color {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, rgb)| distance((r, g, b), *rgb))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::White)
+}
+
+impl Background {
+    fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        let luminance = 0.2126 * f64::from(r) + 0.7152 * f64::from(g) + 0.0722 * f64::from(b);
+        if luminance > 127.5 {
+            Background::Light
+        } else {
+            Background::Dark
+        }
+    }
+}
+
+/// Ask the terminal for its background colour with OSC 11. The query is followed by a request for
+/// the primary device attributes, which every terminal answers, so there's no need to wait for the
+/// timeout on terminals that don't report their background.
+fn query_background(writer: &mut impl Write) -> Result<Option<Background>> {
+    write!(writer, "\x1b]11;?\x1b\\\x1b[c")?;
+    writer.flush()?;
+    let reply = read_reply(QUERY_TIMEOUT)?;
+    Ok(parse_background(&String::from_utf8_lossy(&reply)))
+}
+
+/// Read the terminal's replies up to the device attributes, e.g. `\x1b[?62;22c`.
+#[cfg(unix)]
+fn read_reply(timeout: Duration) -> Result<Vec<u8>> {
+    use std::{os::fd::AsRawFd, time::Instant};
+
+    let fd = std::io::stdin().as_raw_fd();
+    // SAFETY: `isatty` only inspects the file descriptor
+    if unsafe { libc::isatty(fd) } != 1 {
+        return Ok(Vec::new());
+    }
+    let deadline = Instant::now() + timeout;
+    let mut reply = Vec::new();
+    let mut buffer = [0u8; 64];
+    while !(reply.ends_with(b"c") && reply.windows(3).any(|window| window == b"\x1b[?")) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut poll_fd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `poll_fd` is a valid pollfd for the duration of the call
+        let ready = unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+        // SAFETY: `buffer` is valid for writes of its length
+        let read = unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+        if read <= 0 {
+            break;
+        }
+        reply.extend_from_slice(&buffer[..read as usize]);
+    }
+    Ok(reply)
+}
+
+#[cfg(not(unix))]
+fn read_reply(_timeout: Duration) -> Result<Vec<u8>> {
+    Ok(Vec::new())
+}
+
+/// Find the background colour in a reply to OSC 11 like `\x1b]11;rgb:ffff/ffff/ffff\x1b\\`, where
+/// each channel has 1 to 4 hex digits.
+fn parse_background(reply: &str) -> Option<Background> {
+    let start = reply.find("\x1b]11;rgb:")? + "\x1b]11;rgb:".len();
+    let rest = &reply[start..];
+    let end = rest.find(['\x1b', '\x07'])?;
+    let mut channels = rest[..end].split('/').map(|channel| {
+        if channel.is_empty() || channel.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(channel, 16).ok()?;
+        let max = 16u32.pow(channel.len() as u32) - 1;
+        Some((value * 255 / max) as u8)
+    });
+    let (r, g, b) = (channels.next()??, channels.next()??, channels.next()??);
+    Some(Background::from_rgb(r, g, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use ratatui::style::Stylize;
+
+    use super::*;
+
+    #[test]
+    fn test_color_support_from_env() {
+        let detect = ColorSupport::from_env;
+        assert_eq!(detect(None, None, None), ColorSupport::None);
+        assert_eq!(detect(None, None, Some("dumb")), ColorSupport::None);
+        assert_eq!(detect(None, None, Some("xterm")), ColorSupport::Ansi16);
+        assert_eq!(
+            detect(None, None, Some("xterm-256color")),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            detect(None, None, Some("xterm-direct")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            detect(None, Some("truecolor"), Some("xterm-256color")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            detect(Some("1"), Some("truecolor"), Some("xterm-256color")),
+            ColorSupport::None
+        );
+        // an empty `NO_COLOR` is ignored
+        assert_eq!(
+            detect(Some(""), None, Some("xterm-256color")),
+            ColorSupport::Ansi256
+        );
+    }
+
+    #[test]
+    fn test_integrations_from_env() {
+        let detect = |vars: &[(&str, &str)]| {
+            Integrations::from_env(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+        assert_eq!(detect(&[("TERM", "dumb")]), Integrations::default());
+        let kitty = detect(&[("TERM", "xterm-kitty")]);
+        assert!(kitty.title && kitty.hyperlinks && !kitty.progress && !kitty.tmux);
+        assert_eq!(kitty.notifications, Some(Notifications::Osc9));
+        assert_eq!(
+            detect(&[("TERM", "foot")]).notifications,
+            Some(Notifications::Osc777)
+        );
+        // inside tmux, the outer terminal is recognised by the variables it set
+        let tmux = detect(&[
+            ("TERM", "tmux-256color"),
+            ("TERM_PROGRAM", "tmux"),
+            ("TMUX", "/tmp/tmux-1000/default,1234,0"),
+            ("WT_SESSION", "5f1a"),
+        ]);
+        assert!(tmux.tmux && tmux.progress && tmux.hyperlinks);
+        assert_eq!(tmux.notifications, None);
+    }
+
+    #[test]
+    fn test_downgrade_to_256_colors() {
+        let colors = ColorSupport::Ansi256;
+        assert_eq!(
+            colors.downgrade(Color::Rgb(255, 0, 0)),
+            Some(Color::Indexed(196))
+        );
+        assert_eq!(
+            colors.downgrade(Color::Rgb(0, 0, 0)),
+            Some(Color::Indexed(16))
+        );
+        assert_eq!(
+            colors.downgrade(Color::Rgb(128, 128, 128)),
+            Some(Color::Indexed(244))
+        );
+        assert_eq!(colors.downgrade(Color::Red), Some(Color::Red));
+        assert_eq!(
+            colors.downgrade(Color::Indexed(100)),
+            Some(Color::Indexed(100))
+        );
+    }
+
+    #[test]
+    fn test_downgrade_to_16_colors() {
+        let colors = ColorSupport::Ansi16;
+        assert_eq!(
+            colors.downgrade(Color::Rgb(250, 10, 10)),
+            Some(Color::LightRed)
+        );
+        assert_eq!(colors.downgrade(Color::Rgb(20, 20, 20)), Some(Color::Black));
+        assert_eq!(colors.downgrade(Color::Indexed(4)), Some(Color::Blue));
+        assert_eq!(colors.downgrade(Color::Indexed(231)), Some(Color::White));
+        assert_eq!(colors.downgrade(Color::Reset), Some(Color::Reset));
+    }
+
+    #[test]
+    fn test_downgrade_to_no_colors() {
+        let style = Style::new().red().on_blue().bold();
+        assert_eq!(
+            ColorSupport::None.downgrade_style(style),
+            Style::new().bold()
+        );
+        assert_eq!(ColorSupport::TrueColor.downgrade_style(style), style);
+    }
+
+    #[test]
+    fn test_parse_background() {
+        assert_eq!(
+            parse_background("\x1b]11;rgb:ffff/ffff/ffff\x1b\\\x1b[?62c"),
+            Some(Background::Light)
+        );
+        assert_eq!(
+            parse_background("\x1b]11;rgb:1e/1e/2e\x07"),
+            Some(Background::Dark)
+        );
+        assert_eq!(parse_background("\x1b[?62c"), None);
+        assert_eq!(parse_background("\x1b]11;rgb:zz/00/00\x07"), None);
+    }
+}
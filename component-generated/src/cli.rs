@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
 use crate::config::{get_config_dir, get_data_dir};
@@ -9,9 +11,25 @@ pub struct Cli {
     #[arg(short, long, value_name = "FLOAT", default_value_t = 4.0)]
     pub tick_rate: f64,
 
-    /// Frame rate, i.e. number of frames per second
+    /// Frame rate, i.e. the most frames drawn per second. Frames are only drawn when something
+    /// changed.
     #[arg(short, long, value_name = "FLOAT", default_value_t = 60.0)]
     pub frame_rate: f64,
+
+    /// Capture the mouse, overriding the config. `--mouse` on its own turns it on.
+    #[arg(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true")]
+    pub mouse: Option<bool>,
+
+    /// Run the app without a terminal, drawing to the clients attached to the Unix socket at
+    /// SOCKET. It keeps running when they detach.
+    #[cfg(unix)]
+    #[arg(long, value_name = "SOCKET", conflicts_with = "attach")]
+    pub serve: Option<PathBuf>,
+
+    /// Attach the terminal to an app started with `--serve SOCKET`
+    #[cfg(unix)]
+    #[arg(long, value_name = "SOCKET")]
+    pub attach: Option<PathBuf>,
 }
 
 const VERSION_MESSAGE: &str = concat!(
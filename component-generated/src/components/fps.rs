@@ -22,6 +22,9 @@ pub struct FpsCounter {
     last_frame_update: Instant,
     frame_count: u32,
     frames_per_second: f64,
+
+    /// Whether the rates changed since they were last drawn.
+    changed: bool,
 }
 
 impl Default for FpsCounter {
@@ -39,6 +42,7 @@ impl FpsCounter {
             last_frame_update: Instant::now(),
             frame_count: 0,
             frames_per_second: 0.0,
+            changed: true,
         }
     }
 
@@ -50,6 +54,7 @@ impl FpsCounter {
             self.ticks_per_second = self.tick_count as f64 / elapsed;
             self.last_tick_update = now;
             self.tick_count = 0;
+            self.changed = true;
         }
         Ok(())
     }
@@ -62,12 +67,21 @@ impl FpsCounter {
             self.frames_per_second = self.frame_count as f64 / elapsed;
             self.last_frame_update = now;
             self.frame_count = 0;
+            self.changed = true;
         }
         Ok(())
     }
 }
 
 impl Component for FpsCounter {
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn needs_render(&self) -> bool {
+        self.changed
+    }
+
     fn update(&mut self, action: Action) -> Result<Option<Action>> {
         match action {
             Action::Tick => self.app_tick()?,
@@ -86,6 +100,7 @@ impl Component for FpsCounter {
         let span = Span::styled(message, Style::new().dim());
         let paragraph = Paragraph::new(span).right_aligned();
         frame.render_widget(paragraph, top);
+        self.changed = false;
         Ok(())
     }
 }
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+
+use color_eyre::Result;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Clear, Paragraph},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{popup_area, Component};
+use crate::{action::Action, config::Config, mode::Mode};
+
+/// Actions with the key sequences bound to them.
+type Bindings = Vec<(Action, Vec<String>)>;
+
+/// An overlay listing the keybindings of the active mode, generated from the config and grouped
+/// by the category of their action, followed by the bindings of each component that only apply
+/// while it is focused. `Action::Help` opens and closes it.
+#[derive(Default)]
+pub struct Help {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    open: bool,
+    /// The mode whose keybindings are listed, i.e. the active mode other than the help itself.
+    mode: Mode,
+    scroll: u16,
+}
+
+impl Help {
+    fn send(&self, action: Action) -> Result<()> {
+        if let Some(tx) = &self.command_tx {
+            tx.send(action)?;
+        }
+        Ok(())
+    }
+
+    fn toggle(&mut self) -> Result<()> {
+        self.open = !self.open;
+        if self.open {
+            self.scroll = 0;
+            self.send(Action::PushMode(Mode::Help))
+        } else {
+            self.send(Action::PopMode)
+        }
+    }
+
+    fn lines(&self) -> Vec<Line<'static>> {
+        let mut sections: Vec<(String, Bindings)> = Vec::new();
+        let mut categories: BTreeMap<&'static str, Bindings> = BTreeMap::new();
+        for (action, keys) in self.config.bindings_by_action(self.mode) {
+            categories
+                .entry(action.category())
+                .or_default()
+                .push((action, keys));
+        }
+        sections.extend(
+            categories
+                .into_iter()
+                .map(|(category, bindings)| (category.to_string(), bindings)),
+        );
+        let components: BTreeMap<_, _> = self.config.component_keybindings.iter().collect();
+        for (id, keybindings) in components {
+            let bindings = keybindings.by_action(self.mode);
+            if !bindings.is_empty() {
+                sections.push((format!("When {id} is focused"), bindings));
+            }
+        }
+
+        let keys: Vec<Vec<Span>> = sections
+            .iter()
+            .map(|(_, bindings)| {
+                bindings
+                    .iter()
+                    .map(|(_, keys)| Span::raw(keys.join(", ")))
+                    .collect()
+            })
+            .collect();
+        // pad by the width on screen, which differs from the length for keys like `←`
+        let width = keys.iter().flatten().map(Span::width).max().unwrap_or(0);
+        let mut lines = Vec::new();
+        for ((title, bindings), keys) in sections.into_iter().zip(keys) {
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            lines.push(Line::from(title).bold());
+            for ((action, _), keys) in bindings.into_iter().zip(keys) {
+                let padding = " ".repeat(width - keys.width());
+                lines.push(Line::from(vec![
+                    Span::raw("  "),
+                    Span::styled(format!("{}{padding}", keys.content), Style::new().bold()),
+                    Span::raw("  "),
+                    Span::raw(action.description()),
+                ]));
+            }
+        }
+        if lines.is_empty() {
+            lines.push(Line::from(format!("No keybindings for {} mode", self.mode)).dim());
+        }
+        lines
+    }
+}
+
+impl Component for Help {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn hit_test(&self, area: Rect, position: Position) -> bool {
+        self.open && popup_area(area, 80, 80).contains(position)
+    }
+
+    fn handle_mode_enter(&mut self, mode: Mode, stack: &[Mode]) -> Result<Option<Action>> {
+        if mode != Mode::Help {
+            self.mode = mode;
+        }
+        // the help mode was left without closing the help, e.g. by `PopMode`
+        if !stack.contains(&Mode::Help) {
+            self.open = false;
+        }
+        Ok(None)
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::Help => self.toggle()?,
+            Action::ScrollUp(lines) if self.open => self.scroll = self.scroll.saturating_sub(lines),
+            Action::ScrollDown(lines) if self.open => {
+                self.scroll = self.scroll.saturating_add(lines)
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        if !self.open {
+            return Ok(());
+        }
+        let area = popup_area(area, 80, 80);
+        let block = Block::bordered().title(format!("Help: {} mode", self.mode));
+        let lines = self.lines();
+        let max_scroll = (lines.len() as u16).saturating_sub(block.inner(area).height);
+        self.scroll = self.scroll.min(max_scroll);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(lines).block(block).scroll((self.scroll, 0)),
+            area,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_lines_are_grouped_by_category() {
+        let help = Help {
+            config: Config::new().unwrap(),
+            ..Default::default()
+        };
+        let lines: Vec<String> = help.lines().iter().map(ToString::to_string).collect();
+        assert_eq!(lines[0], "Editing");
+        assert!(lines.contains(&"General".to_string()));
+        assert!(lines
+            .iter()
+            .any(|line| line.contains("<ctrl-c>, <ctrl-d>, <q>") && line.contains("Quit")));
+        let navigation = lines.iter().position(|line| line == "Navigation").unwrap();
+        assert!(lines[navigation + 1].contains("Focus the next component"));
+    }
+
+    #[test]
+    fn test_lines_follow_mode() {
+        let mut help = Help {
+            config: Config::new().unwrap(),
+            ..Default::default()
+        };
+        help.handle_mode_enter(Mode::Help, &[Mode::Home, Mode::Help])
+            .unwrap();
+        assert_eq!(help.mode, Mode::Home);
+        help.handle_mode_enter(Mode::CommandPalette, &[Mode::CommandPalette])
+            .unwrap();
+        assert_eq!(help.mode, Mode::CommandPalette);
+        assert!(help.lines()[0].to_string().starts_with("No keybindings"));
+    }
+
+    #[test]
+    fn test_lines_include_component_keybindings() -> Result<()> {
+        let mut config = Config::new()?;
+        config.keybindings = json5::from_str(r#"{ "Home": { "<ä>": "FocusPrevious" } }"#)?;
+        config.component_keybindings =
+            json5::from_str(r#"{ "home": { "Home": { "<Ctrl-Enter>": "Help" } } }"#)?;
+        let help = Help {
+            config,
+            ..Default::default()
+        };
+        let lines: Vec<String> = help.lines().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Navigation",
+                "  <ä>           Focus the previous component",
+                "",
+                "When home is focused",
+                "  <ctrl-enter>  Show or hide the keybindings",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use color_eyre::Result;
+use crossterm::event::KeyEvent;
+use ratatui::{prelude::*, widgets::*};
+
+use super::Component;
+use crate::{action::Action, config::key_sequence_to_string};
+
+/// Shows the keys of a partially typed key sequence and the bindings it can still complete.
+#[derive(Default)]
+pub struct WhichKey {
+    keys: Vec<KeyEvent>,
+    continuations: Vec<(Vec<KeyEvent>, Action)>,
+}
+
+impl Component for WhichKey {
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn hit_test(&self, _area: Rect, _position: Position) -> bool {
+        false
+    }
+
+    fn handle_pending_keys(
+        &mut self,
+        keys: &[KeyEvent],
+        continuations: &[(Vec<KeyEvent>, Action)],
+    ) -> Result<Option<Action>> {
+        self.keys = keys.to_vec();
+        self.continuations = continuations.to_vec();
+        Ok(None)
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        if self.keys.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<Line> = self
+            .continuations
+            .iter()
+            .map(|(keys, action)| {
+                Line::from(vec![
+                    Span::styled(key_sequence_to_string(keys), Style::new().bold()),
+                    Span::raw(" "),
+                    Span::raw(action.to_string()),
+                ])
+            })
+            .collect();
+        let height = (lines.len() as u16).saturating_add(2).min(area.height);
+        let [_, popup] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(height)]).areas(area);
+        let block = Block::bordered().title(key_sequence_to_string(&self.keys));
+        frame.render_widget(Clear, popup);
+        frame.render_widget(Paragraph::new(lines).block(block), popup);
+        Ok(())
+    }
+}
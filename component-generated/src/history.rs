@@ -0,0 +1,262 @@
+#![allow(dead_code)] // Remove this once you start using the code
+
+use std::{
+    any::Any,
+    collections::VecDeque,
+    mem::{self, Discriminant},
+    time::{Duration, Instant},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::action::Action;
+
+/// The most groups of changes kept for undoing; older changes are forgotten.
+const MAX_UNDO: usize = 1000;
+
+/// The longest pause between two changes of a group, after which the next change starts a new
+/// group, so that a single undo doesn't revert a whole session of typing.
+const GROUP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A reversible change made by a component. The change itself can be any type the component
+/// chooses, and is handed back to the component to undo or redo it.
+pub struct Change {
+    component: String,
+    group: Option<&'static str>,
+    data: Box<dyn Any + Send>,
+    /// When the change was made.
+    time: Instant,
+}
+
+impl Change {
+    /// The id of the component that made the change.
+    pub fn component(&self) -> &str {
+        &self.component
+    }
+
+    pub fn data(&self) -> &(dyn Any + Send) {
+        self.data.as_ref()
+    }
+}
+
+/// Lets a component record the reversible changes it makes, usually while handling an action in
+/// `Component::update`.
+#[derive(Clone)]
+pub struct Recorder {
+    component: String,
+    tx: UnboundedSender<Change>,
+}
+
+impl Recorder {
+    pub fn new(component: String, tx: UnboundedSender<Change>) -> Self {
+        Self { component, tx }
+    }
+
+    /// Record a change that is undone on its own.
+    pub fn record<T: Any + Send>(&self, change: T) -> Result<()> {
+        self.send(None, change)
+    }
+
+    /// Record a change that is undone together with the consecutive changes of the same group,
+    /// e.g. every character of a word as it is typed.
+    pub fn record_grouped<T: Any + Send>(&self, group: &'static str, change: T) -> Result<()> {
+        self.send(Some(group), change)
+    }
+
+    fn send<T: Any + Send>(&self, group: Option<&'static str>, change: T) -> Result<()> {
+        self.tx
+            .send(Change {
+                component: self.component.clone(),
+                group,
+                data: Box::new(change),
+                time: Instant::now(),
+            })
+            .map_err(|err| eyre!("Failed to record a change: {err}"))
+    }
+}
+
+/// The changes made by all components, as groups of changes that are undone and redone together.
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    /// Whether the next change starts a new group even if it could join the last one.
+    sealed: bool,
+    /// The kind of the last action handled, see [`History::handling`].
+    action: Option<Discriminant<Action>>,
+}
+
+impl History {
+    /// Add a change, joining the last group if it's from the same component and group and follows
+    /// the last change within [`GROUP_TIMEOUT`]. Any undone changes can no longer be redone.
+    pub fn push(&mut self, change: Change) {
+        self.redo.clear();
+        let sealed = mem::take(&mut self.sealed);
+        if let (Some(changes), false) = (self.undo.back_mut(), sealed) {
+            let joins = changes.last().is_some_and(|last| {
+                change.group.is_some()
+                    && last.group == change.group
+                    && last.component == change.component
+                    && change.time.saturating_duration_since(last.time) <= GROUP_TIMEOUT
+            });
+            if joins {
+                changes.push(change);
+                return;
+            }
+        }
+        self.undo.push_back(vec![change]);
+        if self.undo.len() > MAX_UNDO {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Note that the app is about to handle `action`. Handling a different kind of action than the
+    /// last one ends the current group, e.g. moving the cursor between two runs of typing.
+    pub fn handling(&mut self, action: &Action) {
+        let kind = mem::discriminant(action);
+        if self.action.replace(kind).is_some_and(|last| last != kind) {
+            self.sealed = true;
+        }
+    }
+
+    /// Move the most recent group of changes to the redo stack and return them, most recent
+    /// change first, so they can be undone.
+    pub fn undo(&mut self) -> Option<impl Iterator<Item = &Change>> {
+        let changes = self.undo.pop_back()?;
+        self.sealed = true;
+        self.redo.push(changes);
+        self.redo.last().map(|changes| changes.iter().rev())
+    }
+
+    /// Move the most recently undone group of changes back to the undo stack and return them, in
+    /// the order they were made, so they can be redone.
+    pub fn redo(&mut self) -> Option<impl Iterator<Item = &Change>> {
+        let changes = self.redo.pop()?;
+        self.sealed = true;
+        self.undo.push_back(changes);
+        self.undo.back().map(|changes| changes.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    fn changes(history: &mut History, changes: &[(&str, Option<&'static str>, char)]) {
+        let time = history
+            .undo
+            .back()
+            .and_then(|changes| changes.last())
+            .map_or_else(Instant::now, |change| change.time);
+        changes_at(history, time, changes);
+    }
+
+    fn changes_at(
+        history: &mut History,
+        time: Instant,
+        changes: &[(&str, Option<&'static str>, char)],
+    ) {
+        for (component, group, data) in changes {
+            history.push(Change {
+                component: component.to_string(),
+                group: *group,
+                data: Box::new(*data),
+                time,
+            });
+        }
+    }
+
+    fn data<'a>(changes: impl Iterator<Item = &'a Change>) -> String {
+        changes
+            .map(|change| change.data().downcast_ref::<char>().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = History::default();
+        changes(&mut history, &[("a", None, '1'), ("a", None, '2')]);
+        assert_eq!(history.undo().map(data), Some("2".to_string()));
+        assert_eq!(history.undo().map(data), Some("1".to_string()));
+        assert!(history.undo().is_none());
+        assert_eq!(history.redo().map(data), Some("1".to_string()));
+        assert_eq!(history.redo().map(data), Some("2".to_string()));
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn test_new_change_clears_redo() {
+        let mut history = History::default();
+        changes(&mut history, &[("a", None, '1')]);
+        history.undo();
+        changes(&mut history, &[("a", None, '2')]);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn test_consecutive_changes_in_a_group_are_undone_together() {
+        let mut history = History::default();
+        let typing = Some("typing");
+        changes(
+            &mut history,
+            &[
+                ("a", typing, 'h'),
+                ("a", typing, 'i'),
+                ("b", typing, 'x'),
+                ("a", typing, 'y'),
+                ("a", None, 'z'),
+            ],
+        );
+        assert_eq!(history.undo().map(data), Some("z".to_string()));
+        assert_eq!(history.undo().map(data), Some("y".to_string()));
+        assert_eq!(history.undo().map(data), Some("x".to_string()));
+        assert_eq!(history.undo().map(data), Some("ih".to_string()));
+        assert_eq!(history.redo().map(data), Some("hi".to_string()));
+        // a change after undoing or redoing starts a new group
+        changes(&mut history, &[("a", typing, '!')]);
+        assert_eq!(history.undo().map(data), Some("!".to_string()));
+    }
+
+    #[test]
+    fn test_a_pause_ends_a_group() {
+        let mut history = History::default();
+        let typing = Some("typing");
+        let start = Instant::now();
+        changes_at(&mut history, start, &[("a", typing, 'h')]);
+        changes_at(&mut history, start + GROUP_TIMEOUT, &[("a", typing, 'i')]);
+        let later = start + GROUP_TIMEOUT * 3;
+        changes_at(&mut history, later, &[("a", typing, '!')]);
+        assert_eq!(history.undo().map(data), Some("!".to_string()));
+        assert_eq!(history.undo().map(data), Some("ih".to_string()));
+    }
+
+    #[test]
+    fn test_another_kind_of_action_ends_a_group() {
+        let mut history = History::default();
+        let typing = Some("typing");
+        history.handling(&Action::ScrollDown(1));
+        changes(&mut history, &[("a", typing, 'h')]);
+        history.handling(&Action::ScrollDown(2));
+        changes(&mut history, &[("a", typing, 'i')]);
+        history.handling(&Action::FocusNext);
+        history.handling(&Action::ScrollDown(1));
+        changes(&mut history, &[("a", typing, '!')]);
+        assert_eq!(history.undo().map(data), Some("!".to_string()));
+        assert_eq!(history.undo().map(data), Some("ih".to_string()));
+    }
+
+    #[test]
+    fn test_recorder_sends_changes() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let recorder = Recorder::new("home".to_string(), tx);
+        recorder.record_grouped("typing", 'a').unwrap();
+        let change = rx.try_recv().unwrap();
+        assert_eq!(change.component(), "home");
+        assert_eq!(change.group, Some("typing"));
+        assert_eq!(change.data().downcast_ref::<char>(), Some(&'a'));
+    }
+}
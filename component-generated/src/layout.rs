@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use serde::{de::Deserializer, Deserialize};
+
+/// A node in the layout tree.
+///
+/// A node with a `slot` names an area that a component can be drawn into. A node with `children`
+/// splits its area between them in `direction`, sized by each child's `constraint`. A node can
+/// have both, in which case the slot covers the whole area of the split.
+///
+/// ```json5
+/// "layout": {
+///   "direction": "vertical",
+///   "children": [
+///     { "constraint": "Length(1)", "slot": "fps" },
+///     { "constraint": "Min(0)", "slot": "home" },
+///   ],
+/// },
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct LayoutNode {
+    #[serde(
+        default = "default_constraint",
+        deserialize_with = "deserialize_constraint"
+    )]
+    pub constraint: Constraint,
+    #[serde(default)]
+    pub slot: Option<String>,
+    #[serde(default)]
+    pub direction: LayoutDirection,
+    #[serde(default)]
+    pub children: Vec<LayoutNode>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutDirection {
+    Horizontal,
+    #[default]
+    Vertical,
+}
+
+impl From<LayoutDirection> for Direction {
+    fn from(direction: LayoutDirection) -> Self {
+        match direction {
+            LayoutDirection::Horizontal => Direction::Horizontal,
+            LayoutDirection::Vertical => Direction::Vertical,
+        }
+    }
+}
+
+impl Default for LayoutNode {
+    fn default() -> Self {
+        Self {
+            constraint: default_constraint(),
+            slot: None,
+            direction: LayoutDirection::default(),
+            children: Vec::new(),
+        }
+    }
+}
+
+impl LayoutNode {
+    /// Compute the area of every slot in the tree when the root node is given `area`.
+    pub fn split(&self, area: Rect) -> HashMap<String, Rect> {
+        let mut areas = HashMap::new();
+        self.split_into(area, &mut areas);
+        areas
+    }
+
+    fn split_into(&self, area: Rect, areas: &mut HashMap<String, Rect>) {
+        if let Some(slot) = &self.slot {
+            areas.insert(slot.clone(), area);
+        }
+        if self.children.is_empty() {
+            return;
+        }
+        let constraints = self.children.iter().map(|child| child.constraint);
+        let rects = Layout::new(self.direction.into(), constraints).split(area);
+        for (child, rect) in self.children.iter().zip(rects.iter()) {
+            child.split_into(*rect, areas);
+        }
+    }
+}
+
+fn default_constraint() -> Constraint {
+    Constraint::Fill(1)
+}
+
+fn deserialize_constraint<'de, D>(deserializer: D) -> Result<Constraint, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = String::deserialize(deserializer)?;
+    parse_constraint(&raw).map_err(serde::de::Error::custom)
+}
+
+/// Parse a constraint written like the `Constraint` variant it represents, e.g. `Length(1)`,
+/// `Percentage(50)` or `Ratio(1, 3)`.
+pub fn parse_constraint(raw: &str) -> Result<Constraint, String> {
+    let error = || format!("Unable to parse constraint `{raw}`");
+    let (name, args) = raw
+        .trim()
+        .strip_suffix(')')
+        .and_then(|s| s.split_once('('))
+        .ok_or_else(error)?;
+    let args = args
+        .split(',')
+        .map(|arg| arg.trim().parse::<u16>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| error())?;
+    let constraint = match (name.trim().to_ascii_lowercase().as_str(), args.as_slice()) {
+        ("length", [n]) => Constraint::Length(*n),
+        ("min", [n]) => Constraint::Min(*n),
+        ("max", [n]) => Constraint::Max(*n),
+        ("percentage", [n]) => Constraint::Percentage(*n),
+        ("fill", [n]) => Constraint::Fill(*n),
+        ("ratio", [a, b]) => Constraint::Ratio(u32::from(*a), u32::from(*b)),
+        _ => return Err(error()),
+    };
+    Ok(constraint)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_constraint() {
+        assert_eq!(parse_constraint("Length(1)"), Ok(Constraint::Length(1)));
+        assert_eq!(parse_constraint("min(0)"), Ok(Constraint::Min(0)));
+        assert_eq!(
+            parse_constraint(" Ratio(1, 3) "),
+            Ok(Constraint::Ratio(1, 3))
+        );
+        assert!(parse_constraint("Length").is_err());
+        assert!(parse_constraint("Length(1, 2)").is_err());
+        assert!(parse_constraint("Unknown(1)").is_err());
+    }
+
+    #[test]
+    fn test_split_nested() {
+        let layout: LayoutNode = json5::from_str(
+            r#"{
+                "direction": "vertical",
+                "children": [
+                    { "constraint": "Length(1)", "slot": "status" },
+                    {
+                        "direction": "horizontal",
+                        "slot": "body",
+                        "children": [
+                            { "constraint": "Length(10)", "slot": "sidebar" },
+                            { "slot": "main" },
+                        ],
+                    },
+                ],
+            }"#,
+        )
+        .unwrap();
+        let areas = layout.split(Rect::new(0, 0, 40, 10));
+        assert_eq!(areas["status"], Rect::new(0, 0, 40, 1));
+        assert_eq!(areas["body"], Rect::new(0, 1, 40, 9));
+        assert_eq!(areas["sidebar"], Rect::new(0, 1, 10, 9));
+        assert_eq!(areas["main"], Rect::new(10, 1, 30, 9));
+    }
+
+    #[test]
+    fn test_split_empty_layout() {
+        let areas = LayoutNode::default().split(Rect::new(0, 0, 40, 10));
+        assert!(areas.is_empty());
+    }
+}
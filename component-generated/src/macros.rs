@@ -0,0 +1,156 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::PathBuf};
+
+use color_eyre::Result;
+use tracing::{info, warn};
+
+use crate::{action::Action, config::get_data_dir};
+
+/// Macros recorded by the user, each a list of actions stored in a named register.
+///
+/// The registers are saved as JSON in the data directory whenever a recording stops, so macros
+/// survive restarts.
+#[derive(Debug, Default)]
+pub struct Macros {
+    path: PathBuf,
+    registers: BTreeMap<String, Vec<Action>>,
+    /// The register being recorded into and the actions recorded so far.
+    recording: Option<(String, Vec<Action>)>,
+}
+
+impl Macros {
+    /// Load the macros saved in the data directory, starting with no macros if they can't be
+    /// read.
+    pub fn new() -> Self {
+        let path = get_data_dir().join("macros.json");
+        Self::load(path.clone()).unwrap_or_else(|err| {
+            warn!("Failed to load macros from {}: {err}", path.display());
+            Self {
+                path,
+                ..Self::default()
+            }
+        })
+    }
+
+    /// Load the macros saved at `path`, starting with no macros if the file doesn't exist.
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let registers = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path,
+            registers,
+            recording: None,
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.registers)?)?;
+        Ok(())
+    }
+
+    /// Start recording into `register`, stopping any recording already in progress. The new
+    /// recording starts even if the stopped one couldn't be saved.
+    pub fn start(&mut self, register: &str) -> Result<()> {
+        let saved = self.stop();
+        info!("Recording macro into register {register:?}");
+        self.recording = Some((register.to_string(), Vec::new()));
+        saved
+    }
+
+    /// Stop recording, saving the recorded actions into their register. They stay in the register
+    /// until the app quits even if they couldn't be saved.
+    pub fn stop(&mut self) -> Result<()> {
+        let Some((register, actions)) = self.recording.take() else {
+            return Ok(());
+        };
+        info!(
+            "Recorded {} actions into register {register:?}",
+            actions.len()
+        );
+        self.registers.insert(register, actions);
+        self.save()
+    }
+
+    /// Record `action` if a recording is in progress and the action is recordable.
+    pub fn record(&mut self, action: &Action) {
+        if let Some((_, actions)) = &mut self.recording {
+            if action.is_recordable() {
+                actions.push(action.clone());
+            }
+        }
+    }
+
+    /// The actions of the macro in `register`.
+    pub fn get(&self, register: &str) -> Option<&[Action]> {
+        self.registers.get(register).map(Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::mode::Mode;
+
+    #[test]
+    fn test_record_skips_internal_actions() -> Result<()> {
+        let dir = TempDir::new()?;
+        let mut macros = Macros::load(dir.path().join("macros.json"))?;
+        macros.record(&Action::Quit);
+        macros.start("a")?;
+        macros.record(&Action::Tick);
+        macros.record(&Action::ScrollDown(2));
+        macros.record(&Action::PushMode(Mode::Help));
+        macros.record(&Action::Help);
+        macros.stop()?;
+        assert_eq!(
+            macros.get("a"),
+            Some(&[Action::ScrollDown(2), Action::Help][..])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_macros_are_saved() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("macros.json");
+        let mut macros = Macros::load(path.clone())?;
+        macros.start("a")?;
+        macros.record(&Action::FocusNext);
+        // starting a new recording saves the previous one
+        macros.start("b")?;
+        macros.record(&Action::Error("oops".to_string()));
+        macros.stop()?;
+        let loaded = Macros::load(path)?;
+        assert_eq!(loaded.get("a"), Some(&[Action::FocusNext][..]));
+        assert_eq!(loaded.get("b"), Some(&[][..]));
+        assert_eq!(loaded.get("c"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recording_continues_when_saving_fails() -> Result<()> {
+        let dir = TempDir::new()?;
+        let file = dir.path().join("not-a-dir");
+        fs::write(&file, "")?;
+        let mut macros = Macros {
+            path: file.join("macros.json"),
+            ..Macros::default()
+        };
+        macros.start("a")?;
+        macros.record(&Action::FocusNext);
+        assert!(macros.start("b").is_err());
+        macros.record(&Action::Help);
+        assert!(macros.stop().is_err());
+        assert_eq!(macros.get("a"), Some(&[Action::FocusNext][..]));
+        assert_eq!(macros.get("b"), Some(&[Action::Help][..]));
+        Ok(())
+    }
+}
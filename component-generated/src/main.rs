@@ -6,11 +6,23 @@ use crate::app::App;
 
 mod action;
 mod app;
+mod capabilities;
 mod cli;
 mod components;
 mod config;
 mod errors;
+mod external;
+mod history;
+mod keymap;
+mod layout;
 mod logging;
+mod macros;
+mod mode;
+mod osc;
+#[cfg(unix)]
+mod session;
+mod tasks;
+mod timers;
 mod tui;
 
 #[tokio::main]
@@ -19,7 +31,18 @@ async fn main() -> Result<()> {
     crate::logging::init()?;
 
     let args = Cli::parse();
+    #[cfg(unix)]
+    if let Some(socket) = &args.attach {
+        return session::attach(socket, config::Config::new()?.session.detach).await;
+    }
     let mut app = App::new(args.tick_rate, args.frame_rate)?;
+    if let Some(mouse) = args.mouse {
+        app = app.mouse(mouse);
+    }
+    #[cfg(unix)]
+    if let Some(socket) = &args.serve {
+        return app.serve(socket).await;
+    }
     app.run().await?;
     Ok(())
 }
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+/// The modes the application can be in. Each mode has its own set of keybindings in the config.
+///
+/// Add a variant for each mode your application needs (e.g. `Normal`, `Insert`, `Search`) and a
+/// matching section under `keybindings` in `config.json5`.
+#[derive(
+    Default, Debug, Copy, Clone, PartialEq, Eq, Hash, Display, EnumString, Serialize, Deserialize,
+)]
+pub enum Mode {
+    #[default]
+    Home,
+    /// The command palette is open and receives all keys.
+    CommandPalette,
+    /// The keybinding help is open.
+    Help,
+}
+
+/// A stack of modes where the mode on top of the stack is the active mode.
+///
+/// The bottom of the stack is the base mode and is never popped, so there is always an active
+/// mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeStack(Vec<Mode>);
+
+impl Default for ModeStack {
+    fn default() -> Self {
+        Self::new(Mode::default())
+    }
+}
+
+impl ModeStack {
+    pub fn new(base: Mode) -> Self {
+        Self(vec![base])
+    }
+
+    /// The active mode.
+    pub fn current(&self) -> Mode {
+        *self.0.last().expect("mode stack is never empty")
+    }
+
+    /// All modes on the stack, from the base mode to the active mode.
+    pub fn as_slice(&self) -> &[Mode] {
+        &self.0
+    }
+
+    /// Make `mode` the active mode, keeping the previous mode underneath it.
+    pub fn push(&mut self, mode: Mode) {
+        self.0.push(mode);
+    }
+
+    /// Remove the active mode and return it, unless it is the base mode.
+    pub fn pop(&mut self) -> Option<Mode> {
+        if self.0.len() > 1 {
+            self.0.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Replace the active mode with `mode` and return the mode it replaced.
+    pub fn switch(&mut self, mode: Mode) -> Mode {
+        let top = self.0.last_mut().expect("mode stack is never empty");
+        std::mem::replace(top, mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_default_stack() {
+        let stack = ModeStack::default();
+        assert_eq!(stack.current(), Mode::Home);
+        assert_eq!(stack.as_slice(), &[Mode::Home]);
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut stack = ModeStack::default();
+        stack.push(Mode::CommandPalette);
+        assert_eq!(stack.current(), Mode::CommandPalette);
+        assert_eq!(stack.pop(), Some(Mode::CommandPalette));
+        assert_eq!(stack.current(), Mode::Home);
+        assert_eq!(stack.as_slice().len(), 1);
+    }
+
+    #[test]
+    fn test_base_mode_is_never_popped() {
+        let mut stack = ModeStack::default();
+        assert_eq!(stack.pop(), None);
+        assert_eq!(stack.current(), Mode::Home);
+    }
+
+    #[test]
+    fn test_switch_replaces_top() {
+        let mut stack = ModeStack::default();
+        stack.push(Mode::CommandPalette);
+        assert_eq!(stack.switch(Mode::Home), Mode::CommandPalette);
+        assert_eq!(stack.as_slice(), &[Mode::Home, Mode::Home]);
+    }
+}
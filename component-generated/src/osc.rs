@@ -0,0 +1,185 @@
+//! Escape sequences for terminal integrations beyond drawing: the window title, hyperlinks,
+//! progress, desktop notifications and the bell. Terminals that don't know a sequence may print
+//! it, so each is only sent when [`crate::capabilities::Integrations`] says it's supported.
+
+#![allow(dead_code)] // Remove this once you start using the code
+
+use std::{fmt, str::FromStr};
+
+use ratatui::layout::Rect;
+
+use crate::capabilities::Notifications;
+
+/// Ring the terminal bell, which most terminals turn into an alert on the window or tab.
+pub const BELL: &str = "\x07";
+
+/// Save the current window title, to be restored by [`POP_TITLE`].
+pub const PUSH_TITLE: &str = "\x1b[22;0t";
+
+/// Restore the window title saved by [`PUSH_TITLE`].
+pub const POP_TITLE: &str = "\x1b[23;0t";
+
+/// End the hyperlink started by [`hyperlink`].
+pub const HYPERLINK_END: &str = "\x1b]8;;\x1b\\";
+
+/// Text drawn by a component that links to `url`, e.g. when the terminal is clicked with ctrl.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hyperlink {
+    /// Where the text of the link was drawn.
+    pub area: Rect,
+    pub url: String,
+}
+
+impl Hyperlink {
+    pub fn new(area: Rect, url: impl Into<String>) -> Self {
+        Self {
+            area,
+            url: url.into(),
+        }
+    }
+}
+
+/// The progress of a long running task, shown e.g. in the taskbar or on the tab.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Progress {
+    /// No progress is shown.
+    #[default]
+    Hidden,
+    /// The given percentage of the work is done.
+    Normal(u8),
+    /// Like `Normal`, but shown as failed, e.g. in red.
+    Error(u8),
+    /// Like `Normal`, but shown as paused, e.g. in yellow.
+    Paused(u8),
+    /// Work is going on, but how much is done is unknown.
+    Indeterminate,
+}
+
+/// Progress is written as `hidden`, `indeterminate`, a percentage like `50`, or a percentage
+/// prefixed with `error:` or `paused:`.
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Progress::Hidden => f.write_str("hidden"),
+            Progress::Normal(percent) => write!(f, "{percent}"),
+            Progress::Error(percent) => write!(f, "error:{percent}"),
+            Progress::Paused(percent) => write!(f, "paused:{percent}"),
+            Progress::Indeterminate => f.write_str("indeterminate"),
+        }
+    }
+}
+
+impl FromStr for Progress {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let percent = |raw: &str| match raw.trim_end_matches('%').parse::<u8>() {
+            Ok(percent) if percent <= 100 => Ok(percent),
+            _ => Err(format!(
+                "Expected a percentage from 0 to 100, found `{raw}`"
+            )),
+        };
+        match raw {
+            "hidden" => Ok(Progress::Hidden),
+            "indeterminate" => Ok(Progress::Indeterminate),
+            _ => match raw.split_once(':') {
+                Some(("error", rest)) => percent(rest).map(Progress::Error),
+                Some(("paused", rest)) => percent(rest).map(Progress::Paused),
+                _ => percent(raw).map(Progress::Normal),
+            },
+        }
+    }
+}
+
+/// Set the title of the window or tab.
+pub fn title(title: &str) -> String {
+    format!("\x1b]2;{}\x1b\\", sanitize(title))
+}
+
+/// Start a hyperlink to `url`, which covers the text written until [`HYPERLINK_END`].
+pub fn hyperlink(url: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\", sanitize(url))
+}
+
+/// Show `progress` with OSC 9;4, as introduced by ConEmu.
+pub fn progress(progress: Progress) -> String {
+    let (state, percent) = match progress {
+        Progress::Hidden => (0, 0),
+        Progress::Normal(percent) => (1, percent),
+        Progress::Error(percent) => (2, percent),
+        Progress::Indeterminate => (3, 0),
+        Progress::Paused(percent) => (4, percent),
+    };
+    format!("\x1b]9;4;{state};{percent}\x1b\\")
+}
+
+/// Show a desktop notification with `title` and `body`, which may be empty.
+pub fn notification(kind: Notifications, title: &str, body: &str) -> String {
+    let (title, body) = (sanitize(title), sanitize(body));
+    match kind {
+        Notifications::Osc9 if body.is_empty() => format!("\x1b]9;{title}\x1b\\"),
+        Notifications::Osc9 => format!("\x1b]9;{title}: {body}\x1b\\"),
+        // the title can't contain the separator between the title and the body
+        Notifications::Osc777 => {
+            format!("\x1b]777;notify;{};{body}\x1b\\", title.replace(';', ","))
+        }
+    }
+}
+
+/// Wrap `sequence` so that tmux passes it on to the terminal it runs in, instead of ignoring it.
+/// This needs `set -g allow-passthrough on` in the tmux config.
+pub fn tmux_passthrough(sequence: &str) -> String {
+    format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+}
+
+/// Remove control characters, which would end the sequence early.
+fn sanitize(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_progress_round_trip() {
+        for progress in [
+            Progress::Hidden,
+            Progress::Normal(0),
+            Progress::Normal(100),
+            Progress::Error(42),
+            Progress::Paused(7),
+            Progress::Indeterminate,
+        ] {
+            assert_eq!(progress.to_string().parse(), Ok(progress));
+        }
+        assert_eq!("50%".parse(), Ok(Progress::Normal(50)));
+        assert!("101".parse::<Progress>().is_err());
+        assert!("stalled:5".parse::<Progress>().is_err());
+    }
+
+    #[test]
+    fn test_sequences() {
+        assert_eq!(title("a\x1bb"), "\x1b]2;ab\x1b\\");
+        assert_eq!(progress(Progress::Error(30)), "\x1b]9;4;2;30\x1b\\");
+        assert_eq!(progress(Progress::Hidden), "\x1b]9;4;0;0\x1b\\");
+        assert_eq!(
+            notification(Notifications::Osc9, "Build", "done"),
+            "\x1b]9;Build: done\x1b\\"
+        );
+        assert_eq!(
+            notification(Notifications::Osc777, "a;b", "c;d"),
+            "\x1b]777;notify;a,b;c;d\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_tmux_passthrough() {
+        assert_eq!(
+            tmux_passthrough(&progress(Progress::Normal(5))),
+            "\x1bPtmux;\x1b\x1b]9;4;1;5\x1b\x1b\\\x1b\\"
+        );
+    }
+}
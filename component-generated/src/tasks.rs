@@ -0,0 +1,320 @@
+#![allow(dead_code)] // Remove this once you start using the code
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use color_eyre::{eyre::eyre, Result};
+use tokio::{
+    sync::mpsc::UnboundedSender,
+    task::JoinHandle,
+    time::{timeout_at, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::{action::Action, osc::Progress};
+
+/// How long to wait for the tasks to finish after cancelling them when the app quits, before
+/// aborting them.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Identifies a task started with [`Tasks::spawn`] in the actions about it.
+pub type TaskId = u64;
+
+struct Running {
+    name: String,
+    cancellation_token: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+/// Starts named background tasks for components and keeps track of them until they finish, so
+/// they can be cancelled, e.g. with `Action::CancelTask`, and aren't left running when the app
+/// quits.
+///
+/// Each task gets a child of the app's cancellation token, which is a child of the token of the
+/// [`crate::tui::Tui`] the app runs in. Its progress and result are reported as actions:
+/// `TaskStarted` when it is spawned, then any `TaskProgress`, and finally one of `TaskFinished`,
+/// `TaskFailed` or `TaskCancelled`.
+#[derive(Clone)]
+pub struct Tasks {
+    running: Arc<Mutex<BTreeMap<TaskId, Running>>>,
+    next_id: Arc<AtomicU64>,
+    cancellation_token: CancellationToken,
+    action_tx: UnboundedSender<Action>,
+}
+
+impl Tasks {
+    pub fn new(cancellation_token: CancellationToken, action_tx: UnboundedSender<Action>) -> Self {
+        Self {
+            running: Arc::default(),
+            next_id: Arc::new(AtomicU64::new(1)),
+            cancellation_token,
+            action_tx,
+        }
+    }
+
+    /// Start a task named `name`, shown in the jobs list. The task is given a [`TaskContext`] to
+    /// report its progress with, and the action it returns (if any) is sent before
+    /// `TaskFinished`. A cancelled task is dropped at its next `.await`.
+    pub fn spawn<F, Fut>(&self, name: impl Into<String>, task: F) -> TaskId
+    where
+        F: FnOnce(TaskContext) -> Fut,
+        Fut: Future<Output = Result<Option<Action>>> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let name = name.into();
+        let cancellation_token = self.cancellation_token.child_token();
+        let context = TaskContext {
+            id,
+            cancellation_token: cancellation_token.clone(),
+            action_tx: self.action_tx.clone(),
+        };
+        let future = task(context);
+        let _ = self.action_tx.send(Action::TaskStarted(id, name.clone()));
+        debug!("Starting task {id}: {name}");
+
+        let running = self.running.clone();
+        let action_tx = self.action_tx.clone();
+        let token = cancellation_token.clone();
+        // hold the lock until the task is added, so that it can't remove itself before then
+        let mut tasks = self.lock();
+        let handle = tokio::spawn(async move {
+            let result = tokio::select! {
+                _ = token.cancelled() => None,
+                result = future => Some(result),
+            };
+            let name = running
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .remove(&id)
+                .map(|task| task.name)
+                .unwrap_or_default();
+            let action = match result {
+                None => {
+                    debug!("Task {id} was cancelled: {name}");
+                    Action::TaskCancelled(id)
+                }
+                Some(Ok(action)) => {
+                    debug!("Task {id} finished: {name}");
+                    if let Some(action) = action {
+                        let _ = action_tx.send(action);
+                    }
+                    Action::TaskFinished(id)
+                }
+                Some(Err(err)) => {
+                    warn!("Task {id} failed: {name}: {err}");
+                    Action::TaskFailed(id, err.to_string())
+                }
+            };
+            let _ = action_tx.send(action);
+        });
+        tasks.insert(
+            id,
+            Running {
+                name,
+                cancellation_token,
+                handle,
+            },
+        );
+        id
+    }
+
+    /// Cancel the task with the given id. Returns whether it was still running.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        match self.lock().get(&id) {
+            Some(task) => {
+                task.cancellation_token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel every running task. Tasks can still be started afterwards.
+    pub fn cancel_all(&self) {
+        for task in self.lock().values() {
+            task.cancellation_token.cancel();
+        }
+    }
+
+    /// The id and name of each running task, in the order they were started.
+    pub fn running(&self) -> Vec<(TaskId, String)> {
+        self.lock()
+            .iter()
+            .map(|(id, task)| (*id, task.name.clone()))
+            .collect()
+    }
+
+    /// Cancel every running task and wait for them to finish, aborting those that take longer
+    /// than [`SHUTDOWN_TIMEOUT`].
+    pub async fn shutdown(&self) {
+        self.cancel_all();
+        let handles: Vec<_> = mem::take(&mut *self.lock())
+            .into_values()
+            .map(|task| task.handle)
+            .collect();
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        for mut handle in handles {
+            if timeout_at(deadline, &mut handle).await.is_err() {
+                warn!("A task did not stop within {SHUTDOWN_TIMEOUT:?}, aborting it");
+                handle.abort();
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<TaskId, Running>> {
+        self.running.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Handed to a task started with [`Tasks::spawn`] to report on its progress.
+#[derive(Clone)]
+pub struct TaskContext {
+    id: TaskId,
+    cancellation_token: CancellationToken,
+    action_tx: UnboundedSender<Action>,
+}
+
+impl TaskContext {
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Report how much of the work is done, shown in the jobs list.
+    pub fn progress(&self, progress: Progress) -> Result<()> {
+        self.send(Action::TaskProgress(self.id, progress))
+    }
+
+    /// Send an action to the app while the task runs, e.g. a partial result.
+    pub fn send(&self, action: Action) -> Result<()> {
+        self.action_tx
+            .send(action)
+            .map_err(|err| eyre!("Failed to send an action from task {}: {err}", self.id))
+    }
+
+    /// A token that is cancelled along with the task, for work the task hands off, e.g. to
+    /// `spawn_blocking`, that should check whether to stop.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tokio::sync::{mpsc, oneshot};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_task_reports_progress_and_result() -> Result<()> {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let tasks = Tasks::new(CancellationToken::new(), action_tx);
+        let id = tasks.spawn("count", |task| async move {
+            task.progress(Progress::Normal(50))?;
+            Ok(Some(Action::Notify("Counted".to_string(), String::new())))
+        });
+        let mut actions = Vec::new();
+        while let Some(action) = action_rx.recv().await {
+            let done = action == Action::TaskFinished(id);
+            actions.push(action);
+            if done {
+                break;
+            }
+        }
+        assert_eq!(
+            actions,
+            vec![
+                Action::TaskStarted(id, "count".to_string()),
+                Action::TaskProgress(id, Progress::Normal(50)),
+                Action::Notify("Counted".to_string(), String::new()),
+                Action::TaskFinished(id),
+            ]
+        );
+        assert!(tasks.running().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_task_failure() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let tasks = Tasks::new(CancellationToken::new(), action_tx);
+        let id = tasks.spawn("fail", |_| async { Err(eyre!("no network")) });
+        assert_eq!(
+            action_rx.recv().await,
+            Some(Action::TaskStarted(id, "fail".to_string()))
+        );
+        assert_eq!(
+            action_rx.recv().await,
+            Some(Action::TaskFailed(id, "no network".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_task() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let tasks = Tasks::new(CancellationToken::new(), action_tx);
+        let (_keep_open, never) = oneshot::channel::<()>();
+        let id = tasks.spawn("wait", |_| async move {
+            let _ = never.await;
+            Ok(None)
+        });
+        assert_eq!(tasks.running(), vec![(id, "wait".to_string())]);
+        assert!(tasks.cancel(id));
+        action_rx.recv().await; // TaskStarted
+        assert_eq!(action_rx.recv().await, Some(Action::TaskCancelled(id)));
+        assert!(!tasks.cancel(id));
+    }
+
+    #[tokio::test]
+    async fn test_parent_token_cancels_tasks() {
+        let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+        let parent = CancellationToken::new();
+        let tasks = Tasks::new(parent.child_token(), action_tx);
+        let first = tasks.spawn("first", |task| async move {
+            task.cancellation_token().cancelled().await;
+            Ok(None)
+        });
+        let second = tasks.spawn("second", |task| async move {
+            task.cancellation_token().cancelled().await;
+            Ok(None)
+        });
+        parent.cancel();
+        let mut cancelled = Vec::new();
+        while cancelled.len() < 2 {
+            match action_rx.recv().await {
+                Some(Action::TaskCancelled(id)) => cancelled.push(id),
+                Some(Action::TaskFinished(id)) => cancelled.push(id),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        cancelled.sort();
+        assert_eq!(cancelled, vec![first, second]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_running_tasks() {
+        let (action_tx, _action_rx) = mpsc::unbounded_channel();
+        let tasks = Tasks::new(CancellationToken::new(), action_tx);
+        let (_keep_open, never) = oneshot::channel::<()>();
+        tasks.spawn("wait", |_| async move {
+            let _ = never.await;
+            Ok(None)
+        });
+        tasks.shutdown().await;
+        assert!(tasks.running().is_empty());
+    }
+}
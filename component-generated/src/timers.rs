@@ -0,0 +1,197 @@
+#![allow(dead_code)] // Remove this once you start using the code
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use tokio::{sync::Notify, time::Instant};
+
+use crate::action::Action;
+
+/// The shortest period of a repeating timer, so that a zero period doesn't keep the event loop
+/// busy.
+const MIN_PERIOD: Duration = Duration::from_millis(1);
+
+/// Identifies a timer scheduled with [`Timers`].
+pub type TimerId = u64;
+
+struct Timer {
+    deadline: Instant,
+    /// How often the timer repeats, or none if it only fires once.
+    period: Option<Duration>,
+    action: Action,
+}
+
+#[derive(Default)]
+struct Shared {
+    timers: Mutex<BTreeMap<TimerId, Timer>>,
+    next_id: AtomicU64,
+    /// Notified whenever a timer is scheduled or cancelled, so the event loop can wait for the new
+    /// next deadline.
+    changed: Notify,
+}
+
+/// Sends actions after a delay or at a regular interval, more precisely than counting
+/// `Action::Tick`s. The timers are driven by the event loop of the [`crate::tui::Tui`] they
+/// belong to, which sends each action as an `Event::Timer` when it is due, so they only fire
+/// while the terminal is entered. Timers that came due while the app was suspended fire once it
+/// is resumed, and repeating timers skip the periods they missed.
+#[derive(Clone, Default)]
+pub struct Timers {
+    shared: Arc<Shared>,
+}
+
+impl Timers {
+    /// Send `action` once, after `delay`.
+    pub fn after(&self, delay: Duration, action: Action) -> TimerHandle {
+        self.schedule(delay, None, action)
+    }
+
+    /// Send `action` every `period`, starting one period from now, until the timer is cancelled.
+    pub fn every(&self, period: Duration, action: Action) -> TimerHandle {
+        let period = period.max(MIN_PERIOD);
+        self.schedule(period, Some(period), action)
+    }
+
+    /// Stop the timer with the given id. Returns whether it was still scheduled.
+    pub fn cancel(&self, id: TimerId) -> bool {
+        let cancelled = self.timers().remove(&id).is_some();
+        if cancelled {
+            self.shared.changed.notify_one();
+        }
+        cancelled
+    }
+
+    /// Stop every timer.
+    pub fn cancel_all(&self) {
+        self.timers().clear();
+        self.shared.changed.notify_one();
+    }
+
+    /// Whether the timer with the given id will still fire.
+    pub fn is_scheduled(&self, id: TimerId) -> bool {
+        self.timers().contains_key(&id)
+    }
+
+    /// When the next timer is due, if any are scheduled.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers().values().map(|timer| timer.deadline).min()
+    }
+
+    /// The action of the earliest timer that is due at `now`, if any. A repeating timer is
+    /// scheduled for its next period after `now`, and any other timer is removed.
+    pub fn pop_expired(&self, now: Instant) -> Option<Action> {
+        let mut timers = self.timers();
+        let (&id, _) = timers
+            .iter()
+            .filter(|(_, timer)| timer.deadline <= now)
+            .min_by_key(|(id, timer)| (timer.deadline, **id))?;
+        let timer = timers.get_mut(&id).expect("the timer was just found");
+        match timer.period {
+            Some(period) => {
+                timer.deadline += period;
+                if timer.deadline <= now {
+                    // the app was suspended or fell behind, so skip the missed periods
+                    timer.deadline = now + period;
+                }
+                Some(timer.action.clone())
+            }
+            None => timers.remove(&id).map(|timer| timer.action),
+        }
+    }
+
+    /// Wait until a timer is scheduled or cancelled.
+    pub async fn changed(&self) {
+        self.shared.changed.notified().await;
+    }
+
+    fn schedule(&self, delay: Duration, period: Option<Duration>, action: Action) -> TimerHandle {
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        self.timers().insert(
+            id,
+            Timer {
+                deadline: Instant::now() + delay,
+                period,
+                action,
+            },
+        );
+        self.shared.changed.notify_one();
+        TimerHandle {
+            id,
+            timers: self.clone(),
+        }
+    }
+
+    fn timers(&self) -> MutexGuard<'_, BTreeMap<TimerId, Timer>> {
+        self.shared
+            .timers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// A timer scheduled with [`Timers`]. Dropping the handle leaves the timer scheduled.
+#[derive(Clone)]
+pub struct TimerHandle {
+    id: TimerId,
+    timers: Timers,
+}
+
+impl TimerHandle {
+    pub fn id(&self) -> TimerId {
+        self.id
+    }
+
+    /// Stop the timer. Returns whether it was still scheduled.
+    pub fn cancel(&self) -> bool {
+        self.timers.cancel(self.id)
+    }
+
+    /// Whether the timer will still fire.
+    pub fn is_scheduled(&self) -> bool {
+        self.timers.is_scheduled(self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pop_expired() {
+        let timers = Timers::default();
+        let start = Instant::now();
+        let repeating = timers.every(Duration::from_secs(5), Action::ClearScreen);
+        let once = timers.after(Duration::from_secs(2), Action::Bell);
+        assert_eq!(timers.pop_expired(start), None);
+        assert_eq!(timers.next_deadline(), Some(start + Duration::from_secs(2)));
+
+        let now = start + Duration::from_secs(6);
+        assert_eq!(timers.pop_expired(now), Some(Action::Bell));
+        assert_eq!(timers.pop_expired(now), Some(Action::ClearScreen));
+        assert_eq!(timers.pop_expired(now), None);
+        assert!(!once.is_scheduled());
+        assert!(repeating.is_scheduled());
+        assert_eq!(
+            timers.next_deadline(),
+            Some(start + Duration::from_secs(10))
+        );
+
+        // missed periods are skipped
+        let now = start + Duration::from_secs(31);
+        assert_eq!(timers.pop_expired(now), Some(Action::ClearScreen));
+        assert_eq!(timers.pop_expired(now), None);
+        assert_eq!(timers.next_deadline(), Some(now + Duration::from_secs(5)));
+
+        assert!(repeating.cancel());
+        assert!(!repeating.cancel());
+        assert_eq!(timers.next_deadline(), None);
+    }
+}
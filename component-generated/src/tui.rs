@@ -1,8 +1,13 @@
 #![allow(dead_code)] // Remove this once you start using the code
 
 use std::{
-    io::{stdout, Stdout},
+    io::{stdout, Stdout, Write},
+    mem,
     ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
@@ -10,27 +15,63 @@ use color_eyre::Result;
 use crossterm::{
     cursor,
     event::{
-        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
-        Event as CrosstermEvent, EventStream, KeyEvent, KeyEventKind, MouseEvent,
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, Event as CrosstermEvent, EventStream, KeyEvent,
+        KeyEventKind, KeyboardEnhancementFlags, MouseEvent, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
     },
     terminal::{EnterAlternateScreen, LeaveAlternateScreen},
 };
-use futures::{FutureExt, StreamExt};
-use ratatui::backend::CrosstermBackend as Backend;
+use futures::{future, stream, stream::BoxStream, FutureExt, Stream, StreamExt};
+use ratatui::{
+    backend::{Backend, CrosstermBackend, TestBackend},
+    layout::{Position, Rect},
+    TerminalOptions, Viewport,
+};
 use serde::{Deserialize, Serialize};
 use tokio::{
-    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    sync::{
+        mpsc::{self, error::TrySendError, Receiver, Sender},
+        Notify,
+    },
     task::JoinHandle,
-    time::interval,
+    time::{interval, sleep_until, timeout, Instant, MissedTickBehavior},
 };
 use tokio_util::sync::CancellationToken;
-use tracing::error;
+use tracing::{error, info, warn};
+
+use crate::{
+    action::Action,
+    capabilities::{Capabilities, Integrations},
+    osc::{self, Progress},
+    timers::Timers,
+};
+
+/// The keyboard enhancements requested when [`Features::keyboard`] is enabled: keys that are
+/// ambiguous in the legacy encoding (e.g. `ctrl-i` and `tab`) are told apart, key repeat and
+/// release events are reported, and so are presses of modifier keys like shift on their own.
+/// The shifted character is reported for keys pressed with shift, so `?` is still `?` and not
+/// `shift-/`.
+const KEYBOARD_ENHANCEMENT: KeyboardEnhancementFlags =
+    KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+        .union(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        .union(KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS)
+        .union(KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES);
+
+/// How many events can be queued for the app before reading input waits for the app to catch up.
+/// Ticks, renders and resizes are coalesced instead, see [`Coalesced`].
+pub const EVENT_CAPACITY: usize = 256;
+
+/// How long to wait for the event task to finish when stopping before aborting it.
+const STOP_TIMEOUT: Duration = Duration::from_millis(100);
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Event {
     Init,
     Quit,
     Error,
+    /// The input has ended, e.g. a scripted list of events has run out, so no more input will
+    /// follow.
     Closed,
     Tick,
     Render,
@@ -40,33 +81,423 @@ pub enum Event {
     Key(KeyEvent),
     Mouse(MouseEvent),
     Resize(u16, u16),
+    /// A timer scheduled with [`Tui::timers`] is due. Clients attached to a session can't send
+    /// this, so they can't run arbitrary actions.
+    #[serde(skip)]
+    Timer(Action),
+}
+
+/// Where in the terminal the app is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViewportMode {
+    /// The whole terminal, using the alternate screen.
+    #[default]
+    Fullscreen,
+    /// The given number of lines below the cursor, e.g. below the shell prompt. The last frame is
+    /// left in the scrollback when the app exits.
+    Inline(u16),
+    /// A fixed area of the terminal, without switching to the alternate screen.
+    Fixed(Rect),
+}
+
+impl From<ViewportMode> for Viewport {
+    fn from(mode: ViewportMode) -> Self {
+        match mode {
+            ViewportMode::Fullscreen => Viewport::Fullscreen,
+            ViewportMode::Inline(height) => Viewport::Inline(height),
+            ViewportMode::Fixed(area) => Viewport::Fixed(area),
+        }
+    }
+}
+
+/// The optional terminal features a [`Tui`] enables while it is active.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Features {
+    pub mouse: bool,
+    pub paste: bool,
+    /// Report when the terminal gains or loses focus, as `Event::FocusGained` and
+    /// `Event::FocusLost`.
+    pub focus: bool,
+    /// Draw in the normal screen instead of switching to the alternate screen.
+    pub inline: bool,
+    /// Use the kitty keyboard protocol, if the terminal supports it, to report key repeat and
+    /// release events and keys the legacy encoding can't tell apart. See [`KEYBOARD_ENHANCEMENT`].
+    pub keyboard: bool,
+}
+
+/// The shape of the terminal cursor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CursorShape {
+    /// Whatever shape the user configured their terminal with.
+    #[default]
+    Default,
+    BlinkingBlock,
+    SteadyBlock,
+    BlinkingUnderline,
+    SteadyUnderline,
+    BlinkingBar,
+    SteadyBar,
+}
+
+impl From<CursorShape> for cursor::SetCursorStyle {
+    fn from(shape: CursorShape) -> Self {
+        match shape {
+            CursorShape::Default => cursor::SetCursorStyle::DefaultUserShape,
+            CursorShape::BlinkingBlock => cursor::SetCursorStyle::BlinkingBlock,
+            CursorShape::SteadyBlock => cursor::SetCursorStyle::SteadyBlock,
+            CursorShape::BlinkingUnderline => cursor::SetCursorStyle::BlinkingUnderScore,
+            CursorShape::SteadyUnderline => cursor::SetCursorStyle::SteadyUnderScore,
+            CursorShape::BlinkingBar => cursor::SetCursorStyle::BlinkingBar,
+            CursorShape::SteadyBar => cursor::SetCursorStyle::SteadyBar,
+        }
+    }
+}
+
+/// Where a component wants the terminal cursor shown, e.g. at the insertion point of a text field.
+/// Showing the real cursor there also tells input methods and screen readers where typing goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub position: Position,
+    pub shape: CursorShape,
+}
+
+impl Cursor {
+    pub fn new(position: Position) -> Self {
+        Self {
+            position,
+            shape: CursorShape::Default,
+        }
+    }
+
+    pub fn shape(mut self, shape: CursorShape) -> Self {
+        self.shape = shape;
+        self
+    }
+}
+
+/// A ratatui backend that [`Tui`] can switch into the state the app needs (e.g. raw mode and the
+/// alternate screen) and back again.
+///
+/// Implement this for other backends (e.g. termion or termwiz) to run the app on them.
+pub trait TuiBackend: Backend {
+    fn enter(&mut self, features: Features) -> Result<()>;
+    fn exit(&mut self, features: Features) -> Result<()>;
+    /// Start or stop capturing the mouse while the terminal is entered.
+    fn set_mouse(&mut self, mouse: bool) -> Result<()>;
+    /// Detect what the terminal supports. This is called once the terminal is entered, before
+    /// any events are read.
+    fn capabilities(&mut self) -> Result<Capabilities>;
+    /// Change the shape of the cursor while the terminal is entered.
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()>;
+    /// Write an escape sequence that ratatui doesn't know about, e.g. from [`osc`], straight to
+    /// the terminal.
+    fn write_escape(&mut self, sequence: &str) -> Result<()>;
+    /// Hand the terminal back to the shell until the app is resumed. This is called after the
+    /// terminal is exited, and stops the process with SIGTSTP by default.
+    fn suspend(&mut self) -> Result<()> {
+        #[cfg(not(windows))]
+        signal_hook::low_level::raise(signal_hook::consts::signal::SIGTSTP)?;
+        Ok(())
+    }
+
+    /// Note the area the last frame was drawn into, which is only part of the screen for an inline
+    /// viewport. This is called after every frame, and does nothing by default.
+    fn set_viewport_area(&mut self, _area: Rect) {}
+}
+
+/// The features of the crossterm terminal while a [`Tui`] is active, so that [`restore`] can put
+/// the terminal back without access to the `Tui`.
+static ACTIVE_FEATURES: Mutex<Option<Features>> = Mutex::new(None);
+
+/// The area of the last frame drawn to the crossterm terminal while a [`Tui`] is active, so that
+/// [`restore`] can leave the last frame of an inline viewport in place.
+static VIEWPORT_AREA: Mutex<Option<Rect>> = Mutex::new(None);
+
+impl<W: Write> TuiBackend for CrosstermBackend<W> {
+    fn enter(&mut self, mut features: Features) -> Result<()> {
+        crossterm::terminal::enable_raw_mode()?;
+        if features.keyboard && !crossterm::terminal::supports_keyboard_enhancement()? {
+            info!("The terminal doesn't support keyboard enhancement");
+            features.keyboard = false;
+        }
+        *ACTIVE_FEATURES
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(features);
+        if !features.inline {
+            crossterm::execute!(self, EnterAlternateScreen)?;
+        }
+        crossterm::execute!(self, cursor::Hide)?;
+        if features.mouse {
+            crossterm::execute!(self, EnableMouseCapture)?;
+        }
+        if features.paste {
+            crossterm::execute!(self, EnableBracketedPaste)?;
+        }
+        if features.focus {
+            crossterm::execute!(self, EnableFocusChange)?;
+        }
+        if features.keyboard {
+            crossterm::execute!(self, PushKeyboardEnhancementFlags(KEYBOARD_ENHANCEMENT))?;
+        }
+        Ok(())
+    }
+
+    fn exit(&mut self, features: Features) -> Result<()> {
+        if !crossterm::terminal::is_raw_mode_enabled()? {
+            return Ok(());
+        }
+        Backend::flush(self)?;
+        // the keyboard enhancement was only enabled if the terminal supports it
+        let keyboard = ACTIVE_FEATURES
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .is_some_and(|active| active.keyboard);
+        if features.keyboard && keyboard {
+            crossterm::execute!(self, PopKeyboardEnhancementFlags)?;
+        }
+        if features.focus {
+            crossterm::execute!(self, DisableFocusChange)?;
+        }
+        if features.paste {
+            crossterm::execute!(self, DisableBracketedPaste)?;
+        }
+        if features.mouse {
+            crossterm::execute!(self, DisableMouseCapture)?;
+        }
+        if !features.inline {
+            crossterm::execute!(self, LeaveAlternateScreen)?;
+        }
+        crossterm::execute!(self, cursor::Show)?;
+        crossterm::terminal::disable_raw_mode()?;
+        *ACTIVE_FEATURES
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = None;
+        *VIEWPORT_AREA.lock().unwrap_or_else(|err| err.into_inner()) = None;
+        Ok(())
+    }
+
+    fn set_mouse(&mut self, mouse: bool) -> Result<()> {
+        if mouse {
+            crossterm::execute!(self, EnableMouseCapture)?;
+        } else {
+            crossterm::execute!(self, DisableMouseCapture)?;
+        }
+        if let Some(features) = ACTIVE_FEATURES
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .as_mut()
+        {
+            features.mouse = mouse;
+        }
+        Ok(())
+    }
+
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Capabilities::detect(self)
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        crossterm::execute!(self, cursor::SetCursorStyle::from(shape))?;
+        Ok(())
+    }
+
+    fn write_escape(&mut self, sequence: &str) -> Result<()> {
+        self.write_all(sequence.as_bytes())?;
+        Write::flush(self)?;
+        Ok(())
+    }
+
+    fn set_viewport_area(&mut self, area: Rect) {
+        *VIEWPORT_AREA.lock().unwrap_or_else(|err| err.into_inner()) = Some(area);
+    }
+}
+
+/// Restore the terminal on stdout if a [`Tui`] using crossterm left it in raw mode, e.g. from the
+/// panic hook.
+pub fn restore() -> Result<()> {
+    let features = *ACTIVE_FEATURES
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    let Some(features) = features else {
+        return Ok(());
+    };
+    let mut backend = CrosstermBackend::new(stdout());
+    let viewport = *VIEWPORT_AREA.lock().unwrap_or_else(|err| err.into_inner());
+    if let (true, Some(area)) = (features.inline, viewport) {
+        // leave the last frame in the scrollback, with the panic message below it, as `Tui::exit`
+        // does with the shell prompt
+        backend.set_cursor_position(Position::new(0, area.bottom().saturating_sub(1)))?;
+        backend.append_lines(1)?;
+    }
+    backend.exit(features)?;
+    Ok(())
+}
+
+/// The test backend has no terminal to set up.
+impl TuiBackend for TestBackend {
+    fn enter(&mut self, _features: Features) -> Result<()> {
+        Ok(())
+    }
+
+    fn exit(&mut self, _features: Features) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_mouse(&mut self, _mouse: bool) -> Result<()> {
+        Ok(())
+    }
+
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Ok(Capabilities::default())
+    }
+
+    fn set_cursor_shape(&mut self, _shape: CursorShape) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_escape(&mut self, _sequence: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Where a [`Tui`] reads input events from.
+///
+/// Implement this for the input library of another backend, or use a list of events to run the
+/// app with synthetic input.
+pub trait EventSource {
+    type Events: Stream<Item = Event> + Send + Unpin + 'static;
+
+    /// Start reading events. This is called again whenever the terminal is re-entered, e.g. after
+    /// the app is suspended, and the app stops reading input once the stream ends.
+    fn events(&mut self) -> Self::Events;
+}
+
+/// Reads input events from the terminal using crossterm.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CrosstermEvents;
+
+impl EventSource for CrosstermEvents {
+    type Events = BoxStream<'static, Event>;
+
+    fn events(&mut self) -> Self::Events {
+        // releases and repeats are only passed on when the terminal reports them reliably, i.e.
+        // with keyboard enhancement, and the events are read once the terminal is entered
+        let keyboard = ACTIVE_FEATURES
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .is_some_and(|active| active.keyboard);
+        EventStream::new()
+            .filter_map(move |event| {
+                future::ready(match event {
+                    Ok(CrosstermEvent::Key(key)) if keyboard || key.kind == KeyEventKind::Press => {
+                        Some(Event::Key(key))
+                    }
+                    Ok(CrosstermEvent::Key(_)) => None,
+                    Ok(CrosstermEvent::Mouse(mouse)) => Some(Event::Mouse(mouse)),
+                    Ok(CrosstermEvent::Resize(x, y)) => Some(Event::Resize(x, y)),
+                    Ok(CrosstermEvent::FocusLost) => Some(Event::FocusLost),
+                    Ok(CrosstermEvent::FocusGained) => Some(Event::FocusGained),
+                    Ok(CrosstermEvent::Paste(s)) => Some(Event::Paste(s)),
+                    Err(_) => Some(Event::Error),
+                })
+            })
+            .boxed()
+    }
 }
 
-pub struct Tui {
-    pub terminal: ratatui::Terminal<Backend<Stdout>>,
-    pub task: JoinHandle<()>,
+/// Synthetic input: the events are produced once, in order, and then the input ends.
+impl EventSource for Vec<Event> {
+    type Events = stream::Iter<std::vec::IntoIter<Event>>;
+
+    fn events(&mut self) -> Self::Events {
+        stream::iter(mem::take(self))
+    }
+}
+
+pub struct Tui<B: TuiBackend = CrosstermBackend<Stdout>, I: EventSource = CrosstermEvents> {
+    pub terminal: ratatui::Terminal<B>,
+    pub input: I,
+    /// The task reading events, while it is running.
+    pub task: Option<JoinHandle<()>>,
     pub cancellation_token: CancellationToken,
-    pub event_rx: UnboundedReceiver<Event>,
-    pub event_tx: UnboundedSender<Event>,
+    /// The parent of `cancellation_token`, which unlike it isn't replaced whenever the event task
+    /// is restarted. It's only cancelled when the `Tui` is dropped, so work that should stop along
+    /// with the app, like the tasks of [`crate::tasks::Tasks`], uses a child of it.
+    pub root_token: CancellationToken,
+    pub event_rx: Receiver<Event>,
+    pub event_tx: Sender<Event>,
+    /// The ticks, renders and resizes in the event queue.
+    coalesced: Arc<Coalesced>,
+    /// Timers that send an action after a delay or at an interval, as `Event::Timer`.
+    pub timers: Timers,
+    /// Notified when the app wants a frame drawn, see [`Tui::request_render`].
+    pub render_requested: Arc<Notify>,
+    /// Listens for the signals that ask the app to quit, from the first time the event task starts
+    /// until the `Tui` is dropped. It's kept across restarts so that a SIGTERM or SIGHUP received
+    /// while the task is stopped, e.g. while the app is suspended, is still delivered.
+    quit_signals: Arc<tokio::sync::Mutex<Option<QuitSignals>>>,
+    /// The most frames drawn per second.
     pub frame_rate: f64,
     pub tick_rate: f64,
-    pub mouse: bool,
-    pub paste: bool,
+    pub features: Features,
+    /// Whether the terminal has been entered and not yet exited.
+    pub active: bool,
+    /// What the terminal supports, detected when it is first entered.
+    pub capabilities: Option<Capabilities>,
+    /// The shape of the cursor while the terminal is entered. It's put back to the user's default
+    /// whenever the terminal is exited.
+    pub cursor_shape: CursorShape,
+    /// The window title set by the app, which replaces the title the terminal had while the
+    /// terminal is entered.
+    pub title: Option<String>,
+    /// The progress shown while the terminal is entered.
+    pub progress: Progress,
 }
 
 impl Tui {
+    /// A full screen terminal user interface on stdout, using crossterm.
     pub fn new() -> Result<Self> {
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        Self::with_viewport(ViewportMode::Fullscreen)
+    }
+
+    /// A terminal user interface on stdout drawn in the given viewport, using crossterm.
+    pub fn with_viewport(viewport: ViewportMode) -> Result<Self> {
+        Self::with_backend(CrosstermBackend::new(stdout()), CrosstermEvents, viewport)
+    }
+}
+
+impl<B: TuiBackend, I: EventSource> Tui<B, I> {
+    /// A terminal user interface drawing to `backend` in the given viewport and reading events
+    /// from `input`.
+    pub fn with_backend(backend: B, input: I, viewport: ViewportMode) -> Result<Self> {
+        let (event_tx, event_rx) = mpsc::channel(EVENT_CAPACITY);
+        let root_token = CancellationToken::new();
+        let options = TerminalOptions {
+            viewport: viewport.into(),
+        };
         Ok(Self {
-            terminal: ratatui::Terminal::new(Backend::new(stdout()))?,
-            task: tokio::spawn(async {}),
-            cancellation_token: CancellationToken::new(),
+            terminal: ratatui::Terminal::with_options(backend, options)?,
+            input,
+            task: None,
+            cancellation_token: root_token.child_token(),
+            root_token,
             event_rx,
             event_tx,
+            coalesced: Arc::new(Coalesced::default()),
+            timers: Timers::default(),
+            render_requested: Arc::new(Notify::new()),
+            quit_signals: Arc::default(),
             frame_rate: 60.0,
             tick_rate: 4.0,
-            mouse: false,
-            paste: false,
+            features: Features {
+                inline: viewport != ViewportMode::Fullscreen,
+                ..Features::default()
+            },
+            active: false,
+            capabilities: None,
+            cursor_shape: CursorShape::Default,
+            title: None,
+            progress: Progress::Hidden,
         })
     }
 
@@ -81,115 +512,201 @@ impl Tui {
     }
 
     pub fn mouse(mut self, mouse: bool) -> Self {
-        self.mouse = mouse;
+        self.features.mouse = mouse;
         self
     }
 
     pub fn paste(mut self, paste: bool) -> Self {
-        self.paste = paste;
+        self.features.paste = paste;
         self
     }
 
-    pub fn start(&mut self) {
-        self.cancel(); // Cancel any existing task
-        self.cancellation_token = CancellationToken::new();
-        let event_loop = Self::event_loop(
-            self.event_tx.clone(),
-            self.cancellation_token.clone(),
-            self.tick_rate,
-            self.frame_rate,
-        );
-        self.task = tokio::spawn(async {
-            event_loop.await;
-        });
-    }
-
-    async fn event_loop(
-        event_tx: UnboundedSender<Event>,
-        cancellation_token: CancellationToken,
-        tick_rate: f64,
-        frame_rate: f64,
-    ) {
-        let mut event_stream = EventStream::new();
-        let mut tick_interval = interval(Duration::from_secs_f64(1.0 / tick_rate));
-        let mut render_interval = interval(Duration::from_secs_f64(1.0 / frame_rate));
+    pub fn focus_change(mut self, focus: bool) -> Self {
+        self.features.focus = focus;
+        self
+    }
 
-        // if this fails, then it's likely a bug in the calling code
-        event_tx
-            .send(Event::Init)
-            .expect("failed to send init event");
-        loop {
-            let event = tokio::select! {
-                _ = cancellation_token.cancelled() => {
-                    break;
-                }
-                _ = tick_interval.tick() => Event::Tick,
-                _ = render_interval.tick() => Event::Render,
-                crossterm_event = event_stream.next().fuse() => match crossterm_event {
-                    Some(Ok(event)) => match event {
-                        CrosstermEvent::Key(key) if key.kind == KeyEventKind::Press => Event::Key(key),
-                        CrosstermEvent::Mouse(mouse) => Event::Mouse(mouse),
-                        CrosstermEvent::Resize(x, y) => Event::Resize(x, y),
-                        CrosstermEvent::FocusLost => Event::FocusLost,
-                        CrosstermEvent::FocusGained => Event::FocusGained,
-                        CrosstermEvent::Paste(s) => Event::Paste(s),
-                        _ => continue, // ignore other events
+    /// Report key repeat and release events, and keys like `ctrl-i` separately from `tab`, if the
+    /// terminal supports the kitty keyboard protocol.
+    pub fn keyboard_enhancement(mut self, keyboard: bool) -> Self {
+        self.features.keyboard = keyboard;
+        self
+    }
+
+    /// Start or stop capturing the mouse, straight away if the terminal is entered.
+    pub fn set_mouse(&mut self, mouse: bool) -> Result<()> {
+        if self.active && self.features.mouse != mouse {
+            self.terminal.backend_mut().set_mouse(mouse)?;
+        }
+        self.features.mouse = mouse;
+        Ok(())
+    }
+
+    /// Change the shape of the cursor, straight away if the terminal is entered.
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        if self.active && self.cursor_shape != shape {
+            self.terminal.backend_mut().set_cursor_shape(shape)?;
+        }
+        self.cursor_shape = shape;
+        Ok(())
+    }
+
+    /// Set the title of the terminal window or tab, or put back the title it had before the app
+    /// set one with `None`.
+    pub fn set_title(&mut self, title: Option<String>) -> Result<()> {
+        if self.active && self.integrations().title {
+            let had_title = self.title.is_some();
+            match &title {
+                Some(title) => {
+                    if !had_title {
+                        self.write_integration(osc::PUSH_TITLE, false)?;
                     }
-                    Some(Err(_)) => Event::Error,
-                    None => break, // the event stream has stopped and will not produce any more events
-                },
-            };
-            if event_tx.send(event).is_err() {
-                // the receiver has been dropped, so there's no point in continuing the loop
-                break;
+                    self.write_integration(&osc::title(title), false)?;
+                }
+                None if had_title => self.write_integration(osc::POP_TITLE, false)?,
+                None => {}
             }
         }
-        cancellation_token.cancel();
+        self.title = title;
+        Ok(())
+    }
+
+    /// Show the progress of a long running task, e.g. in the taskbar or on the tab.
+    pub fn set_progress(&mut self, progress: Progress) -> Result<()> {
+        if self.active && self.integrations().progress && self.progress != progress {
+            self.write_integration(&osc::progress(progress), true)?;
+        }
+        self.progress = progress;
+        Ok(())
+    }
+
+    /// Show a desktop notification, e.g. when a long running task is done. Terminals that can't
+    /// show notifications ring the bell instead, which usually marks the window or tab.
+    pub fn notify(&mut self, title: &str, body: &str) -> Result<()> {
+        match self.integrations().notifications {
+            Some(kind) => self.write_integration(&osc::notification(kind, title, body), true),
+            None => self.bell(),
+        }
+    }
+
+    pub fn bell(&mut self) -> Result<()> {
+        self.write_integration(osc::BELL, false)
+    }
+
+    /// The integrations the terminal supports, none until it is first entered.
+    fn integrations(&self) -> Integrations {
+        self.capabilities.unwrap_or_default().integrations
+    }
+
+    /// Write an escape sequence, wrapped so that tmux passes it on to the terminal if
+    /// `passthrough` is set. Nothing is written unless the terminal is entered.
+    fn write_integration(&mut self, sequence: &str, passthrough: bool) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        if passthrough && self.integrations().tmux {
+            self.terminal
+                .backend_mut()
+                .write_escape(&osc::tmux_passthrough(sequence))
+        } else {
+            self.terminal.backend_mut().write_escape(sequence)
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.cancel(); // Cancel any existing task
+        self.cancellation_token = self.root_token.child_token();
+        let event_loop = EventLoop {
+            event_tx: self.event_tx.clone(),
+            coalesced: self.coalesced.clone(),
+            timers: self.timers.clone(),
+            cancellation_token: self.cancellation_token.clone(),
+            render_requested: self.render_requested.clone(),
+            quit_signals: self.quit_signals.clone(),
+            tick_rate: self.tick_rate,
+            frame_rate: self.frame_rate,
+        };
+        let events = self.input.events();
+        self.task = Some(tokio::spawn(async {
+            event_loop.run(events).await;
+        }));
     }
 
-    pub fn stop(&self) -> Result<()> {
+    /// Ask for an `Event::Render`, which is sent as soon as the frame rate allows. Requests made
+    /// before the event is sent are combined into one.
+    pub fn request_render(&self) {
+        self.render_requested.notify_one();
+    }
+
+    /// Stop reading events, waiting for the event task to finish and aborting it if it takes
+    /// longer than [`STOP_TIMEOUT`].
+    pub async fn stop(&mut self) -> Result<()> {
         self.cancel();
-        let mut counter = 0;
-        while !self.task.is_finished() {
-            std::thread::sleep(Duration::from_millis(1));
-            counter += 1;
-            if counter > 50 {
-                self.task.abort();
-            }
-            if counter > 100 {
-                error!("Failed to abort task in 100 milliseconds for unknown reason");
-                break;
+        let Some(mut task) = self.task.take() else {
+            return Ok(());
+        };
+        match timeout(STOP_TIMEOUT, &mut task).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) if err.is_cancelled() => {}
+            Ok(Err(err)) => error!("Event task failed: {err}"),
+            Err(_) => {
+                warn!("Event task did not stop within {STOP_TIMEOUT:?}, aborting it");
+                task.abort();
             }
         }
         Ok(())
     }
 
     pub fn enter(&mut self) -> Result<()> {
-        crossterm::terminal::enable_raw_mode()?;
-        crossterm::execute!(stdout(), EnterAlternateScreen, cursor::Hide)?;
-        if self.mouse {
-            crossterm::execute!(stdout(), EnableMouseCapture)?;
+        self.terminal.backend_mut().enter(self.features)?;
+        if self.cursor_shape != CursorShape::Default {
+            self.terminal
+                .backend_mut()
+                .set_cursor_shape(self.cursor_shape)?;
         }
-        if self.paste {
-            crossterm::execute!(stdout(), EnableBracketedPaste)?;
+        self.active = true;
+        if self.capabilities.is_none() {
+            self.capabilities = Some(self.terminal.backend_mut().capabilities()?);
         }
+        if let Some(title) = self.title.take() {
+            self.set_title(Some(title))?;
+        }
+        let progress = mem::take(&mut self.progress);
+        self.set_progress(progress)?;
         self.start();
         Ok(())
     }
 
+    /// Restore the terminal. This can't wait for the event task, so call [`Tui::stop`] first to
+    /// let it finish; otherwise it is aborted.
     pub fn exit(&mut self) -> Result<()> {
-        self.stop()?;
-        if crossterm::terminal::is_raw_mode_enabled()? {
-            self.flush()?;
-            if self.paste {
-                crossterm::execute!(stdout(), DisableBracketedPaste)?;
-            }
-            if self.mouse {
-                crossterm::execute!(stdout(), DisableMouseCapture)?;
-            }
-            crossterm::execute!(stdout(), LeaveAlternateScreen, cursor::Show)?;
-            crossterm::terminal::disable_raw_mode()?;
+        self.cancel();
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        if !self.active {
+            return Ok(());
+        }
+        // put back the title and remove the progress, but keep them for when the terminal is
+        // entered again
+        let (title, progress) = (self.title.clone(), self.progress);
+        self.set_title(None)?;
+        self.set_progress(Progress::Hidden)?;
+        (self.title, self.progress) = (title, progress);
+        self.active = false;
+        if self.features.inline {
+            // leave the last frame in the scrollback, with the shell prompt on the line below it
+            let area = self.terminal.get_frame().area();
+            self.terminal
+                .set_cursor_position(Position::new(0, area.bottom().saturating_sub(1)))?;
+            self.terminal.backend_mut().append_lines(1)?;
+        }
+        if self.cursor_shape != CursorShape::Default {
+            self.terminal
+                .backend_mut()
+                .set_cursor_shape(CursorShape::Default)?;
         }
+        self.terminal.backend_mut().exit(self.features)?;
         Ok(())
     }
 
@@ -197,39 +714,445 @@ impl Tui {
         self.cancellation_token.cancel();
     }
 
-    pub fn suspend(&mut self) -> Result<()> {
+    pub async fn suspend(&mut self) -> Result<()> {
+        self.stop().await?;
         self.exit()?;
-        #[cfg(not(windows))]
-        signal_hook::low_level::raise(signal_hook::consts::signal::SIGTSTP)?;
+        self.terminal.backend_mut().suspend()?;
         Ok(())
     }
 
     pub fn resume(&mut self) -> Result<()> {
         self.enter()?;
+        if self.features.inline {
+            // the shell moved the cursor while the app was suspended, so start a new viewport
+            // below it
+            let size = self.terminal.size()?;
+            self.terminal
+                .resize(Rect::new(0, 0, size.width, size.height))?;
+        }
         Ok(())
     }
 
+    /// The next event, waiting for one if none is queued. There is at most one tick, render and
+    /// resize in the queue at a time, and a resize always has the latest size.
     pub async fn next_event(&mut self) -> Option<Event> {
-        self.event_rx.recv().await
+        let event = self.event_rx.recv().await?;
+        Some(self.coalesced.received(event))
+    }
+
+    /// The next event if one is already queued, without waiting for one.
+    pub fn try_next_event(&mut self) -> Option<Event> {
+        let event = self.event_rx.try_recv().ok()?;
+        Some(self.coalesced.received(event))
+    }
+}
+
+/// Keeps at most one tick, render and resize in the event queue, so that an app that falls behind
+/// doesn't work through a backlog of them: ticks are dropped, renders are combined, and a queued
+/// resize is updated to the latest size.
+#[derive(Debug, Default)]
+struct Coalesced {
+    tick: AtomicBool,
+    render: AtomicBool,
+    /// The latest size, while a resize is queued.
+    resize: Mutex<Option<(u16, u16)>>,
+}
+
+impl Coalesced {
+    /// Whether `event` needs to be queued, or is covered by an event that already is.
+    fn queue(&self, event: &Event) -> bool {
+        match *event {
+            Event::Tick => !self.tick.swap(true, Ordering::AcqRel),
+            Event::Render => !self.render.swap(true, Ordering::AcqRel),
+            Event::Resize(width, height) => self
+                .resize
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .replace((width, height))
+                .is_none(),
+            _ => true,
+        }
+    }
+
+    /// Take `event` out of the queue, or forget it if it couldn't be queued after all. A resize
+    /// is given the latest size.
+    fn received(&self, event: Event) -> Event {
+        match event {
+            Event::Tick => self.tick.store(false, Ordering::Release),
+            Event::Render => self.render.store(false, Ordering::Release),
+            Event::Resize(width, height) => {
+                let (width, height) = self
+                    .resize
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .take()
+                    .unwrap_or((width, height));
+                return Event::Resize(width, height);
+            }
+            _ => {}
+        }
+        event
+    }
+}
+
+/// What the event task needs from the [`Tui`] that starts it.
+struct EventLoop {
+    event_tx: Sender<Event>,
+    coalesced: Arc<Coalesced>,
+    timers: Timers,
+    cancellation_token: CancellationToken,
+    render_requested: Arc<Notify>,
+    quit_signals: Arc<tokio::sync::Mutex<Option<QuitSignals>>>,
+    tick_rate: f64,
+    frame_rate: f64,
+}
+
+impl EventLoop {
+    /// Send the events from `events` to `event_tx` until cancelled, along with tick events at the
+    /// tick rate, a render event whenever one is requested, at most at the frame rate, and a timer
+    /// event whenever one of `timers` is due.
+    ///
+    /// When the queue is full, input is left unread until the app catches up, and ticks are
+    /// dropped.
+    async fn run(self, mut events: impl Stream<Item = Event> + Unpin) {
+        let EventLoop {
+            event_tx,
+            coalesced,
+            timers,
+            cancellation_token,
+            render_requested,
+            quit_signals,
+            tick_rate,
+            frame_rate,
+        } = self;
+        let mut tick_interval = interval(Duration::from_secs_f64(1.0 / tick_rate));
+        // if the app falls behind, skip the missed ticks instead of sending them all at once
+        tick_interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let frame_duration = Duration::from_secs_f64(1.0 / frame_rate);
+        let mut next_frame = Instant::now();
+        let mut render_pending = false;
+        // held until the task ends, which only waits if the previous task is still stopping
+        let mut listening = quit_signals.lock_owned().await;
+        let quit_signals = listening.get_or_insert_with(QuitSignals::new);
+        quit_signals.restart();
+
+        // if this fails, then it's likely a bug in the calling code
+        event_tx
+            .send(Event::Init)
+            .await
+            .expect("failed to send init event");
+        loop {
+            let next_timer = timers.next_deadline();
+            let event = tokio::select! {
+                _ = cancellation_token.cancelled() => {
+                    break;
+                }
+                signal = quit_signals.recv() => {
+                    if quit_signals.received > 1 {
+                        // the app didn't quit after the first signal, so don't wait for it any longer
+                        error!("Received {signal} again, exiting immediately");
+                        let _ = restore();
+                        std::process::exit(libc::EXIT_FAILURE);
+                    }
+                    info!("Received {signal}, quitting");
+                    Event::Quit
+                }
+                _ = tick_interval.tick() => Event::Tick,
+                _ = render_requested.notified(), if !render_pending => {
+                    render_pending = true;
+                    continue;
+                }
+                _ = sleep_until(next_frame), if render_pending => {
+                    render_pending = false;
+                    next_frame = Instant::now() + frame_duration;
+                    Event::Render
+                }
+                _ = sleep_until(next_timer.unwrap_or_else(Instant::now)), if next_timer.is_some() => {
+                    match timers.pop_expired(Instant::now()) {
+                        Some(action) => Event::Timer(action),
+                        None => continue,
+                    }
+                }
+                // a timer was scheduled or cancelled, so wait for the new next deadline
+                _ = timers.changed() => continue,
+                event = events.next().fuse() => match event {
+                    Some(event) => event,
+                    None => {
+                        // the event stream has stopped and will not produce any more events
+                        tokio::select! {
+                            _ = cancellation_token.cancelled() => {}
+                            _ = event_tx.send(Event::Closed) => {}
+                        }
+                        break;
+                    }
+                },
+            };
+            if !coalesced.queue(&event) {
+                continue;
+            }
+            let sent = match event {
+                Event::Tick => match event_tx.try_send(event) {
+                    Ok(()) => true,
+                    Err(TrySendError::Full(event)) => {
+                        // the app is behind, so it doesn't need another tick
+                        coalesced.received(event);
+                        true
+                    }
+                    Err(TrySendError::Closed(_)) => false,
+                },
+                event => tokio::select! {
+                    _ = cancellation_token.cancelled() => {
+                        coalesced.received(event);
+                        break;
+                    }
+                    result = event_tx.send(event.clone()) => result.is_ok(),
+                },
+            };
+            if !sent {
+                // the receiver has been dropped, so there's no point in continuing the loop
+                break;
+            }
+        }
+        cancellation_token.cancel();
     }
 }
 
-impl Deref for Tui {
-    type Target = ratatui::Terminal<Backend<Stdout>>;
+/// The signals that ask the app to quit: SIGINT, SIGTERM and SIGHUP on unix, or Ctrl-C elsewhere.
+/// Listening for them replaces their default handling, which would kill the app without
+/// restoring the terminal.
+struct QuitSignals {
+    #[cfg(unix)]
+    signals: Vec<(&'static str, tokio::signal::unix::Signal)>,
+    /// How many signals were received.
+    received: usize,
+}
+
+impl QuitSignals {
+    fn new() -> Self {
+        #[cfg(unix)]
+        let signals = {
+            use tokio::signal::unix::{signal, SignalKind};
+            [
+                ("SIGINT", SignalKind::interrupt()),
+                ("SIGTERM", SignalKind::terminate()),
+                ("SIGHUP", SignalKind::hangup()),
+            ]
+            .into_iter()
+            .filter_map(|(name, kind)| match signal(kind) {
+                Ok(signal) => Some((name, signal)),
+                Err(err) => {
+                    warn!("Unable to listen for {name}: {err}");
+                    None
+                }
+            })
+            .collect()
+        };
+        Self {
+            #[cfg(unix)]
+            signals,
+            received: 0,
+        }
+    }
+
+    /// Start counting the signals again when the event task starts. A SIGINT received while the
+    /// task was stopped came from Ctrl-C pressed in the shell or the external editor the terminal
+    /// was handed to, which share the app's process group, so it is dropped rather than quitting
+    /// the app as soon as it resumes.
+    fn restart(&mut self) {
+        #[cfg(unix)]
+        for (name, signal) in self.signals.iter_mut() {
+            if *name == "SIGINT" {
+                while let Some(Some(())) = signal.recv().now_or_never() {}
+            }
+        }
+        self.received = 0;
+    }
+
+    /// Wait for the next signal and return its name.
+    async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        let name = {
+            if self.signals.is_empty() {
+                future::pending::<()>().await;
+            }
+            let signals = self.signals.iter_mut().map(|(name, signal)| {
+                let name = *name;
+                Box::pin(async move {
+                    signal.recv().await;
+                    name
+                })
+            });
+            future::select_all(signals).await.0
+        };
+        #[cfg(not(unix))]
+        let name = {
+            if tokio::signal::ctrl_c().await.is_err() {
+                future::pending::<()>().await;
+            }
+            "Ctrl-C"
+        };
+        self.received += 1;
+        name
+    }
+}
+
+impl<B: TuiBackend, I: EventSource> Deref for Tui<B, I> {
+    type Target = ratatui::Terminal<B>;
 
     fn deref(&self) -> &Self::Target {
         &self.terminal
     }
 }
 
-impl DerefMut for Tui {
+impl<B: TuiBackend, I: EventSource> DerefMut for Tui<B, I> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.terminal
     }
 }
 
-impl Drop for Tui {
+impl<B: TuiBackend, I: EventSource> Drop for Tui<B, I> {
     fn drop(&mut self) {
         self.exit().unwrap();
+        self.root_token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stop_waits_for_event_task() -> Result<()> {
+        let events = vec![Event::Key(KeyEvent::from(crossterm::event::KeyCode::Enter))];
+        let mut tui =
+            Tui::with_backend(TestBackend::new(20, 10), events, ViewportMode::Fullscreen)?;
+        tui.enter()?;
+        assert!(tui.task.is_some());
+        tui.stop().await?;
+        assert!(tui.task.is_none());
+        assert!(tui.cancellation_token.is_cancelled());
+        assert!(!tui.root_token.is_cancelled());
+        tui.exit()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dropping_tui_cancels_child_tokens() -> Result<()> {
+        let tui = Tui::with_backend(
+            TestBackend::new(20, 10),
+            Vec::new(),
+            ViewportMode::Fullscreen,
+        )?;
+        let child = tui.root_token.child_token();
+        drop(tui);
+        assert!(child.is_cancelled());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inline_viewport() -> Result<()> {
+        let backend = TestBackend::new(20, 10);
+        let mut tui = Tui::with_backend(backend, Vec::new(), ViewportMode::Inline(3))?;
+        assert!(tui.features.inline);
+        tui.enter()?;
+        assert_eq!(tui.get_frame().area(), Rect::new(0, 0, 20, 3));
+        tui.exit()?;
+        assert!(!tui.active);
+        Ok(())
+    }
+
+    #[test]
+    fn test_coalesced_events() {
+        let key = Event::Key(KeyEvent::from(crossterm::event::KeyCode::Enter));
+        let coalesced = Coalesced::default();
+        assert!(coalesced.queue(&Event::Tick));
+        assert!(!coalesced.queue(&Event::Tick));
+        assert!(coalesced.queue(&Event::Resize(10, 5)));
+        assert!(!coalesced.queue(&Event::Resize(20, 8)));
+        assert!(coalesced.queue(&key));
+        assert!(coalesced.queue(&key));
+        assert!(matches!(
+            coalesced.received(Event::Resize(10, 5)),
+            Event::Resize(20, 8)
+        ));
+        assert!(coalesced.queue(&Event::Resize(30, 9)));
+        coalesced.received(Event::Tick);
+        assert!(coalesced.queue(&Event::Tick));
+    }
+
+    #[tokio::test]
+    async fn test_input_waits_for_a_full_queue() -> Result<()> {
+        let key = Event::Key(KeyEvent::from(crossterm::event::KeyCode::Enter));
+        let events = vec![key; EVENT_CAPACITY * 2];
+        let mut tui =
+            Tui::with_backend(TestBackend::new(20, 10), events, ViewportMode::Fullscreen)?;
+        tui.enter()?;
+        let mut keys = 0;
+        while keys < EVENT_CAPACITY * 2 {
+            match timeout(Duration::from_secs(1), tui.next_event()).await? {
+                Some(Event::Key(_)) => keys += 1,
+                Some(_) => {}
+                None => break,
+            }
+        }
+        assert_eq!(keys, EVENT_CAPACITY * 2);
+        tui.exit()?;
+        Ok(())
+    }
+
+    /// Input that never produces an event, so the event loop keeps running.
+    struct NoInput;
+
+    impl EventSource for NoInput {
+        type Events = stream::Pending<Event>;
+
+        fn events(&mut self) -> Self::Events {
+            stream::pending()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timers_fire_in_the_event_loop() -> Result<()> {
+        let mut tui =
+            Tui::with_backend(TestBackend::new(20, 10), NoInput, ViewportMode::Fullscreen)?;
+        let start = Instant::now();
+        let once = tui.timers.after(Duration::from_secs(2), Action::Bell);
+        let cancelled = tui.timers.after(Duration::from_secs(3), Action::Quit);
+        let repeating = tui
+            .timers
+            .every(Duration::from_secs(5), Action::ClearScreen);
+        tui.enter()?;
+        assert!(cancelled.cancel());
+        let mut fired = Vec::new();
+        while fired.len() < 3 {
+            if let Some(Event::Timer(action)) = tui.next_event().await {
+                fired.push((action, start.elapsed()));
+            }
+        }
+        assert_eq!(
+            fired,
+            vec![
+                (Action::Bell, Duration::from_secs(2)),
+                (Action::ClearScreen, Duration::from_secs(5)),
+                (Action::ClearScreen, Duration::from_secs(10)),
+            ]
+        );
+        assert!(!once.is_scheduled());
+        assert!(repeating.is_scheduled());
+        tui.stop().await?;
+        tui.exit()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_viewport_mode_config() {
+        let modes: Vec<ViewportMode> =
+            json5::from_str(r#"["Fullscreen", { "Inline": 8 }]"#).unwrap();
+        assert_eq!(
+            modes,
+            vec![ViewportMode::Fullscreen, ViewportMode::Inline(8)]
+        );
     }
 }
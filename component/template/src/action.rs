@@ -1,7 +1,22 @@
-use serde::{Deserialize, Serialize};
-use strum::Display;
+use std::{fmt, str::FromStr};
 
-#[derive(Debug, Clone, PartialEq, Eq, Display, Serialize, Deserialize)]
+use serde::{
+    de::{self, Deserializer},
+    Deserialize, Serialize, Serializer,
+};
+use serde_json::Value;
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
+
+use crate::{mode::Mode, osc::Progress, tasks::TaskId};
+
+/// Actions are written as the name of the action followed by its arguments, separated by
+/// whitespace, e.g. `Quit`, `ScrollDown 5` or `SwitchMode Home`. Text arguments that contain
+/// whitespace are quoted, e.g. `Error "Something went wrong"`. This form is used for logging and
+/// is accepted by `FromStr`, so every action can be written out and read back again.
+///
+/// In the config, an action can also be written as a map from the name to its argument or list of
+/// arguments, e.g. `{ "ScrollDown": 5 }` or `{ "Resize": [80, 24] }`.
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, IntoStaticStr)]
 pub enum Action {
     Tick,
     Render,
@@ -12,4 +27,577 @@ pub enum Action {
     ClearScreen,
     Error(String),
     Help,
+    /// Replace the active mode.
+    SwitchMode(Mode),
+    /// Make a mode active, returning to the current mode on `PopMode`.
+    PushMode(Mode),
+    /// Return to the mode that was active before the last `PushMode`.
+    PopMode,
+    /// Give focus to the next focusable component.
+    FocusNext,
+    /// Give focus to the previous focusable component.
+    FocusPrevious,
+    /// Give focus to the component with the given id.
+    Focus(String),
+    /// Scroll up by the given number of lines (1 if omitted).
+    ScrollUp(u16),
+    /// Scroll down by the given number of lines (1 if omitted).
+    ScrollDown(u16),
+    /// Open the command palette to search for an action to run.
+    OpenCommandPalette,
+    /// Start recording the actions that follow into the named register, replacing its macro.
+    RecordMacro(String),
+    /// Stop recording and save the macro.
+    StopRecording,
+    /// Replay the macro in the named register the given number of times (1 if omitted).
+    PlayMacro(String, u16),
+    /// Undo the most recent change recorded by a component.
+    Undo,
+    /// Redo the most recently undone change.
+    Redo,
+    /// Start or stop capturing the mouse.
+    ToggleMouse,
+    /// Set the title of the terminal window or tab.
+    SetTitle(String),
+    /// Put back the title the terminal had before `SetTitle`.
+    RestoreTitle,
+    /// Show the progress of a long running task, e.g. in the taskbar: `hidden`, `indeterminate`,
+    /// a percentage like `50`, or a percentage prefixed with `error:` or `paused:`.
+    Progress(Progress),
+    /// Show a desktop notification with the given title and body (empty if omitted), or ring the
+    /// bell if the terminal can't show notifications.
+    Notify(String, String),
+    /// Ring the terminal bell.
+    Bell,
+    /// Suspend the app to edit the given text (empty if omitted) in the user's editor, then send
+    /// the result to the component with the given id as `Edited`.
+    Edit(String, String),
+    /// Like `Edit`, but with the given command instead of the user's editor. The path of the file
+    /// to edit is added as the last argument of the command.
+    EditWith(String, String, String),
+    /// The text edited for the component with the given id.
+    Edited(String, String),
+    /// A background task with the given id and name was started.
+    TaskStarted(TaskId, String),
+    /// How much of the work of the task with the given id is done.
+    TaskProgress(TaskId, Progress),
+    /// The task with the given id finished.
+    TaskFinished(TaskId),
+    /// The task with the given id failed with the given error.
+    TaskFailed(TaskId, String),
+    /// The task with the given id stopped because it was cancelled.
+    TaskCancelled(TaskId),
+    /// Cancel the background task with the given id.
+    CancelTask(TaskId),
+    /// Cancel every running background task.
+    CancelTasks,
+}
+
+impl Action {
+    /// Every action that can be run on its own, i.e. it takes no arguments and isn't only sent
+    /// internally by the app.
+    pub fn commands() -> impl Iterator<Item = Action> {
+        Action::iter().filter(|action| {
+            action.args().is_empty()
+                && !matches!(action, Action::Tick | Action::Render | Action::Resume)
+        })
+    }
+
+    /// The name of the action, without its arguments.
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+
+    /// A short description of what the action does, shown in the help.
+    pub fn description(&self) -> String {
+        match self {
+            Action::Tick => "Advance timers and animations".to_string(),
+            Action::Render => "Draw the screen".to_string(),
+            Action::Resize(width, height) => format!("Resize the screen to {width}x{height}"),
+            Action::Suspend => "Suspend the application".to_string(),
+            Action::Resume => "Resume the application".to_string(),
+            Action::Quit => "Quit the application".to_string(),
+            Action::ClearScreen => "Clear and redraw the screen".to_string(),
+            Action::Error(message) => format!("Report an error: {message}"),
+            Action::Help => "Show or hide the keybindings".to_string(),
+            Action::SwitchMode(mode) => format!("Switch to {mode} mode"),
+            Action::PushMode(mode) => format!("Enter {mode} mode"),
+            Action::PopMode => "Return to the previous mode".to_string(),
+            Action::FocusNext => "Focus the next component".to_string(),
+            Action::FocusPrevious => "Focus the previous component".to_string(),
+            Action::Focus(id) => format!("Focus {id}"),
+            Action::ScrollUp(1) => "Scroll up".to_string(),
+            Action::ScrollUp(lines) => format!("Scroll up {lines} lines"),
+            Action::ScrollDown(1) => "Scroll down".to_string(),
+            Action::ScrollDown(lines) => format!("Scroll down {lines} lines"),
+            Action::OpenCommandPalette => "Search for an action to run".to_string(),
+            Action::RecordMacro(register) => format!("Record a macro into register {register}"),
+            Action::StopRecording => "Stop recording the macro".to_string(),
+            Action::PlayMacro(register, 1) => format!("Play the macro in register {register}"),
+            Action::PlayMacro(register, count) => {
+                format!("Play the macro in register {register} {count} times")
+            }
+            Action::Undo => "Undo the last change".to_string(),
+            Action::Redo => "Redo the last undone change".to_string(),
+            Action::ToggleMouse => "Turn mouse support on or off".to_string(),
+            Action::SetTitle(title) => format!("Set the window title to {title}"),
+            Action::RestoreTitle => "Restore the window title".to_string(),
+            Action::Progress(Progress::Hidden) => "Hide the progress".to_string(),
+            Action::Progress(progress) => format!("Show progress {progress}"),
+            Action::Notify(title, _) => format!("Notify: {title}"),
+            Action::Bell => "Ring the bell".to_string(),
+            Action::Edit(id, _) => format!("Edit text for {id} in the external editor"),
+            Action::EditWith(id, command, _) => format!("Edit text for {id} with `{command}`"),
+            Action::Edited(id, _) => format!("Send edited text to {id}"),
+            Action::TaskStarted(_, name) => format!("Started {name}"),
+            Action::TaskProgress(id, progress) => format!("Task {id} progress {progress}"),
+            Action::TaskFinished(id) => format!("Task {id} finished"),
+            Action::TaskFailed(id, error) => format!("Task {id} failed: {error}"),
+            Action::TaskCancelled(id) => format!("Task {id} was cancelled"),
+            Action::CancelTask(id) => format!("Cancel task {id}"),
+            Action::CancelTasks => "Cancel all background tasks".to_string(),
+        }
+    }
+
+    /// The group the action is listed under in the help.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Action::SwitchMode(_) | Action::PushMode(_) | Action::PopMode => "Modes",
+            Action::FocusNext
+            | Action::FocusPrevious
+            | Action::Focus(_)
+            | Action::ScrollUp(_)
+            | Action::ScrollDown(_) => "Navigation",
+            Action::RecordMacro(_) | Action::StopRecording | Action::PlayMacro(..) => "Macros",
+            Action::Undo | Action::Redo => "Editing",
+            Action::SetTitle(_)
+            | Action::RestoreTitle
+            | Action::Progress(_)
+            | Action::Notify(..)
+            | Action::Bell => "Terminal",
+            Action::TaskStarted(..)
+            | Action::TaskProgress(..)
+            | Action::TaskFinished(_)
+            | Action::TaskFailed(..)
+            | Action::TaskCancelled(_)
+            | Action::CancelTask(_)
+            | Action::CancelTasks => "Tasks",
+            _ => "General",
+        }
+    }
+
+    /// Whether the action is recorded into a macro. Actions only sent internally are skipped, as
+    /// are the actions components send to open and close overlays like the command palette, to
+    /// edit text externally or to signal the terminal, so that replaying a macro only repeats what
+    /// the user asked for.
+    pub fn is_recordable(&self) -> bool {
+        !matches!(
+            self,
+            Action::Tick
+                | Action::Render
+                | Action::Resize(..)
+                | Action::Resume
+                | Action::Error(_)
+                | Action::PushMode(_)
+                | Action::PopMode
+                | Action::Focus(_)
+                | Action::OpenCommandPalette
+                | Action::RecordMacro(_)
+                | Action::StopRecording
+                | Action::PlayMacro(..)
+                | Action::Edit(..)
+                | Action::EditWith(..)
+                | Action::Edited(..)
+                | Action::SetTitle(_)
+                | Action::RestoreTitle
+                | Action::Progress(_)
+                | Action::Notify(..)
+                | Action::Bell
+                | Action::TaskStarted(..)
+                | Action::TaskProgress(..)
+                | Action::TaskFinished(_)
+                | Action::TaskFailed(..)
+                | Action::TaskCancelled(_)
+                | Action::CancelTask(_)
+        )
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self {
+            Action::Resize(width, height) => vec![width.to_string(), height.to_string()],
+            Action::Error(text)
+            | Action::Focus(text)
+            | Action::RecordMacro(text)
+            | Action::SetTitle(text) => vec![quote(text)],
+            Action::PlayMacro(register, count) => vec![quote(register), count.to_string()],
+            Action::Edit(id, text) | Action::Edited(id, text) => vec![quote(id), quote(text)],
+            Action::Notify(title, body) => vec![quote(title), quote(body)],
+            Action::Progress(progress) => vec![progress.to_string()],
+            Action::EditWith(id, command, text) => vec![quote(id), quote(command), quote(text)],
+            Action::SwitchMode(mode) | Action::PushMode(mode) => vec![mode.to_string()],
+            Action::ScrollUp(lines) | Action::ScrollDown(lines) => vec![lines.to_string()],
+            Action::TaskStarted(id, text) | Action::TaskFailed(id, text) => {
+                vec![id.to_string(), quote(text)]
+            }
+            Action::TaskProgress(id, progress) => vec![id.to_string(), progress.to_string()],
+            Action::TaskFinished(id) | Action::TaskCancelled(id) | Action::CancelTask(id) => {
+                vec![id.to_string()]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+        for arg in self.args() {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(raw)?;
+        let Some((name, args)) = tokens.split_first() else {
+            return Err("Expected an action, found an empty string".to_string());
+        };
+        let mut args = Args {
+            action: name,
+            args: args.iter(),
+        };
+        let action = match name.as_str() {
+            "Resize" => Action::Resize(args.required()?, args.required()?),
+            "Error" => Action::Error(args.required()?),
+            "SwitchMode" => Action::SwitchMode(args.required()?),
+            "PushMode" => Action::PushMode(args.required()?),
+            "Focus" => Action::Focus(args.required()?),
+            "ScrollUp" => Action::ScrollUp(args.optional(1)?),
+            "ScrollDown" => Action::ScrollDown(args.optional(1)?),
+            "RecordMacro" => Action::RecordMacro(args.required()?),
+            "PlayMacro" => Action::PlayMacro(args.required()?, args.optional(1)?),
+            "Edit" => Action::Edit(args.required()?, args.optional(String::new())?),
+            "EditWith" => Action::EditWith(
+                args.required()?,
+                args.required()?,
+                args.optional(String::new())?,
+            ),
+            "Edited" => Action::Edited(args.required()?, args.required()?),
+            "SetTitle" => Action::SetTitle(args.required()?),
+            "Progress" => Action::Progress(args.required()?),
+            "Notify" => Action::Notify(args.required()?, args.optional(String::new())?),
+            "TaskStarted" => Action::TaskStarted(args.required()?, args.required()?),
+            "TaskProgress" => Action::TaskProgress(args.required()?, args.required()?),
+            "TaskFinished" => Action::TaskFinished(args.required()?),
+            "TaskFailed" => Action::TaskFailed(args.required()?, args.required()?),
+            "TaskCancelled" => Action::TaskCancelled(args.required()?),
+            "CancelTask" => Action::CancelTask(args.required()?),
+            name => Action::iter()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| format!("Unknown action `{name}`"))?,
+        };
+        args.finish()?;
+        Ok(action)
+    }
+}
+
+/// The arguments of an action being parsed.
+struct Args<'a> {
+    action: &'a str,
+    args: std::slice::Iter<'a, String>,
+}
+
+impl Args<'_> {
+    fn required<T>(&mut self) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let Some(arg) = self.args.next() else {
+            return Err(format!("`{}` is missing an argument", self.action));
+        };
+        arg.parse()
+            .map_err(|err| format!("Invalid argument `{arg}` for `{}`: {err}", self.action))
+    }
+
+    fn optional<T>(&mut self, default: T) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        if self.args.len() == 0 {
+            return Ok(default);
+        }
+        self.required()
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        match self.args.next() {
+            Some(arg) => Err(format!("Unexpected argument `{arg}` for `{}`", self.action)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Quote text that would otherwise not be read back as a single argument.
+fn quote(text: &str) -> String {
+    if text.is_empty() || text.starts_with('"') || text.contains(char::is_whitespace) {
+        serde_json::to_string(text).expect("strings can always be serialized")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Split an action into whitespace separated tokens, where quoted tokens use JSON string syntax.
+fn tokenize(raw: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = raw.trim_start();
+    while !rest.is_empty() {
+        let end = if rest.starts_with('"') {
+            let mut escaped = false;
+            let close = rest.char_indices().skip(1).find(|&(_, c)| {
+                let found = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                found
+            });
+            let Some((close, _)) = close else {
+                return Err(format!("Unterminated quote in `{raw}`"));
+            };
+            let token = &rest[..=close];
+            tokens.push(
+                serde_json::from_str(token)
+                    .map_err(|err| format!("Invalid quoted text {token} in `{raw}`: {err}"))?,
+            );
+            close + 1
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tokens.push(rest[..end].to_string());
+            end
+        };
+        rest = rest[end..].trim_start();
+    }
+    Ok(tokens)
+}
+
+impl Serialize for Action {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = match Value::deserialize(deserializer)? {
+            Value::String(raw) => raw,
+            Value::Object(map) if map.len() == 1 => {
+                let (name, args) = map.into_iter().next().expect("map has one entry");
+                let args = match args {
+                    Value::Array(args) => args,
+                    Value::Null => Vec::new(),
+                    arg => vec![arg],
+                };
+                let mut raw = name;
+                for arg in args {
+                    raw.push(' ');
+                    match arg {
+                        Value::String(text) => raw.push_str(&quote(&text)),
+                        arg => raw.push_str(&arg.to_string()),
+                    }
+                }
+                raw
+            }
+            other => {
+                return Err(de::Error::custom(format!(
+                    "Expected an action like \"Quit\" or {{ \"ScrollDown\": 5 }}, found {other}"
+                )))
+            }
+        };
+        raw.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Action::Quit.to_string(), "Quit");
+        assert_eq!(Action::Resize(80, 24).to_string(), "Resize 80 24");
+        assert_eq!(
+            Action::SwitchMode(Mode::Home).to_string(),
+            "SwitchMode Home"
+        );
+        assert_eq!(
+            Action::Error("it broke".to_string()).to_string(),
+            r#"Error "it broke""#
+        );
+    }
+
+    #[test]
+    fn test_description() {
+        assert_eq!(Action::Quit.description(), "Quit the application");
+        assert_eq!(Action::ScrollDown(1).description(), "Scroll down");
+        assert_eq!(Action::ScrollDown(5).description(), "Scroll down 5 lines");
+        assert_eq!(
+            Action::SwitchMode(Mode::Home).description(),
+            "Switch to Home mode"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("Quit".parse(), Ok(Action::Quit));
+        assert_eq!("  ScrollDown   5 ".parse(), Ok(Action::ScrollDown(5)));
+        assert_eq!("ScrollDown".parse(), Ok(Action::ScrollDown(1)));
+        assert_eq!("PushMode Home".parse(), Ok(Action::PushMode(Mode::Home)));
+        assert_eq!(
+            "PlayMacro a".parse(),
+            Ok(Action::PlayMacro("a".to_string(), 1))
+        );
+        assert_eq!(
+            "Edit notes".parse(),
+            Ok(Action::Edit("notes".to_string(), String::new()))
+        );
+        assert_eq!(
+            r#"EditWith notes "code --wait" "some text""#.parse(),
+            Ok(Action::EditWith(
+                "notes".to_string(),
+                "code --wait".to_string(),
+                "some text".to_string()
+            ))
+        );
+        assert_eq!(
+            "Progress paused:20".parse(),
+            Ok(Action::Progress(Progress::Paused(20)))
+        );
+        assert_eq!(
+            "Notify Done".parse(),
+            Ok(Action::Notify("Done".to_string(), String::new()))
+        );
+        assert_eq!(
+            "TaskProgress 3 error:40".parse(),
+            Ok(Action::TaskProgress(3, Progress::Error(40)))
+        );
+        assert_eq!("CancelTask 7".parse(), Ok(Action::CancelTask(7)));
+        assert_eq!(
+            r#"Error "say \"hi\"""#.parse(),
+            Ok(Action::Error(r#"say "hi""#.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "Jump".parse::<Action>(),
+            Err("Unknown action `Jump`".to_string())
+        );
+        assert_eq!(
+            "Quit now".parse::<Action>(),
+            Err("Unexpected argument `now` for `Quit`".to_string())
+        );
+        assert_eq!(
+            "Resize 80".parse::<Action>(),
+            Err("`Resize` is missing an argument".to_string())
+        );
+        assert!("ScrollDown many".parse::<Action>().is_err());
+        assert!("SwitchMode Nowhere".parse::<Action>().is_err());
+        assert!(r#"Error "unterminated"#.parse::<Action>().is_err());
+        assert!("".parse::<Action>().is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let texts = [
+            "",
+            "one",
+            "two words",
+            "\"quoted\"",
+            "tab\there",
+            "back\\slash",
+            "two\nlines\n",
+        ];
+        let actions = Action::iter()
+            .chain(texts.iter().map(|text| Action::Error(text.to_string())))
+            .chain(texts.iter().map(|text| Action::Focus(text.to_string())))
+            .chain(
+                texts
+                    .iter()
+                    .map(|text| Action::PlayMacro(text.to_string(), 3)),
+            )
+            .chain(
+                texts
+                    .iter()
+                    .map(|text| Action::Edited("notes".to_string(), text.to_string())),
+            )
+            .chain(texts.iter().map(|text| Action::SetTitle(text.to_string())))
+            .chain(
+                texts
+                    .iter()
+                    .map(|text| Action::Notify("Done".to_string(), text.to_string())),
+            )
+            .chain([
+                Action::Progress(Progress::Error(50)),
+                Action::Progress(Progress::Indeterminate),
+                Action::TaskProgress(2, Progress::Normal(75)),
+            ])
+            .chain(
+                texts
+                    .iter()
+                    .map(|text| Action::TaskFailed(4, text.to_string())),
+            );
+        for action in actions {
+            assert_eq!(action.to_string().parse(), Ok(action));
+        }
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let actions: Vec<Action> = json5::from_str(
+            r#"[
+                "Quit",
+                "ScrollDown 5",
+                { "ScrollUp": 2 },
+                { "Resize": [80, 24] },
+                { "Error": "it broke" },
+                { "Help": null },
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                Action::Quit,
+                Action::ScrollDown(5),
+                Action::ScrollUp(2),
+                Action::Resize(80, 24),
+                Action::Error("it broke".to_string()),
+                Action::Help,
+            ]
+        );
+        assert!(json5::from_str::<Action>(r#"{ "ScrollDown": "lots" }"#).is_err());
+        assert!(json5::from_str::<Action>("5").is_err());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let json = serde_json::to_string(&Action::Resize(80, 24)).unwrap();
+        assert_eq!(json, r#""Resize 80 24""#);
+        assert_eq!(
+            serde_json::from_str::<Action>(&json).unwrap(),
+            Action::Resize(80, 24)
+        );
+    }
 }
@@ -0,0 +1,148 @@
+use std::sync::{Arc, Mutex};
+
+use color_eyre::{eyre::eyre, Result};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    backend::TestBackend,
+    buffer::Buffer,
+    layout::{Position, Rect},
+    Frame, Terminal,
+};
+
+use super::App;
+use crate::{action::Action, components::Component, config::parse_key_sequence, tui::Event};
+
+/// A component that records every action the app handles, since each one is sent to every
+/// component. It draws nothing and never takes focus or the mouse.
+#[derive(Clone, Default)]
+pub struct ActionLog(Arc<Mutex<Vec<Action>>>);
+
+impl ActionLog {
+    /// Add the log to the components of `app`, before the app is initialised.
+    pub fn attach(&self, app: &mut App) {
+        app.components
+            .push(("action-log".to_string(), Box::new(self.clone())));
+    }
+
+    /// Every action handled so far, in the order it was handled.
+    pub fn actions(&self) -> Vec<Action> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+}
+
+impl Component for ActionLog {
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn hit_test(&self, _area: Rect, _position: Position) -> bool {
+        false
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(action);
+        Ok(None)
+    }
+
+    fn draw(&mut self, _frame: &mut Frame, _area: Rect) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs an [`App`] without a terminal for tests. Each scripted event is handled along with every
+/// action that follows from it, the way the main loop does, and frames are drawn to a
+/// [`TestBackend`] so the rendered buffer can be checked.
+pub struct TestApp {
+    pub app: App,
+    pub terminal: Terminal<TestBackend>,
+    log: ActionLog,
+}
+
+impl TestApp {
+    pub fn new(width: u16, height: u16) -> Result<Self> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+        let mut app = App::new(4.0, 60.0)?;
+        let log = ActionLog::default();
+        log.attach(&mut app);
+        app.init(terminal.get_frame().area())?;
+        let mut test_app = Self { app, terminal, log };
+        test_app.handle_actions()?;
+        Ok(test_app)
+    }
+
+    /// Handle an event and every action that follows from it.
+    pub fn event(&mut self, event: Event) -> Result<()> {
+        if let Event::Resize(width, height) = event {
+            self.terminal.backend_mut().resize(width, height);
+        }
+        self.app.handle_event(event)?;
+        self.handle_actions()
+    }
+
+    /// Handle each of the events in turn, stopping early if the app quits.
+    pub fn events(&mut self, events: impl IntoIterator<Item = Event>) -> Result<()> {
+        for event in events {
+            if self.app.should_quit {
+                break;
+            }
+            self.event(event)?;
+        }
+        Ok(())
+    }
+
+    /// Press the keys of a key sequence written as in the config, e.g. `<ctrl-p>` or `<g><g>`.
+    pub fn keys(&mut self, raw: &str) -> Result<()> {
+        let keys = parse_key_sequence(raw).map_err(|err| eyre!(err))?;
+        self.events(keys.into_iter().map(Event::Key))
+    }
+
+    /// Type text one character at a time.
+    pub fn type_text(&mut self, text: &str) -> Result<()> {
+        self.events(
+            text.chars()
+                .map(|c| Event::Key(KeyEvent::from(KeyCode::Char(c)))),
+        )
+    }
+
+    /// Let a partially typed key sequence time out, running the action bound to it (if any).
+    pub fn timeout(&mut self) -> Result<()> {
+        self.app.handle_key_sequence_timeout()?;
+        self.handle_actions()
+    }
+
+    /// Draw a frame and return the buffer it was drawn into.
+    pub fn render(&mut self) -> Result<&Buffer> {
+        self.event(Event::Render)?;
+        Ok(self.terminal.backend().buffer())
+    }
+
+    /// The text of the last frame, one line per row with trailing whitespace removed.
+    pub fn screen(&self) -> String {
+        let buffer = self.terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width.max(1) as usize)
+            .map(|row| {
+                let line: String = row.iter().map(|cell| cell.symbol()).collect();
+                line.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Every action handled so far, in the order it was handled.
+    pub fn actions(&self) -> Vec<Action> {
+        self.log.actions()
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.app.should_quit
+    }
+
+    fn handle_actions(&mut self) -> Result<()> {
+        self.app.handle_actions(&mut self.terminal)
+    }
+}
@@ -1,15 +1,28 @@
 use color_eyre::Result;
 use crossterm::event::{KeyEvent, MouseEvent};
 use ratatui::{
-    layout::{Rect, Size},
+    layout::{Constraint, Flex, Layout, Position, Rect, Size},
     Frame,
 };
 use tokio::sync::mpsc::UnboundedSender;
 
-use crate::{action::Action, config::Config, tui::Event};
+use crate::{
+    action::Action,
+    config::Config,
+    history::{Change, Recorder},
+    mode::Mode,
+    osc::Hyperlink,
+    tasks::Tasks,
+    timers::Timers,
+    tui::{Cursor, Event},
+};
 
+pub mod command_palette;
 pub mod fps;
+pub mod help;
 pub mod home;
+pub mod jobs;
+pub mod which_key;
 
 /// `Component` is a trait that represents a visual and interactive element of the user interface.
 ///
@@ -42,6 +55,48 @@ pub trait Component {
         let _ = config; // to appease clippy
         Ok(())
     }
+    /// Register a recorder for the reversible changes the component makes, so they can be undone
+    /// with `Action::Undo` and redone with `Action::Redo`.
+    ///
+    /// # Arguments
+    ///
+    /// * `recorder` - A recorder for the changes made by this component.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - An Ok result or an error.
+    fn register_history_handler(&mut self, recorder: Recorder) -> Result<()> {
+        let _ = recorder; // to appease clippy
+        Ok(())
+    }
+    /// Register a task manager for starting background work, e.g. loading data, instead of
+    /// spawning it directly, so that it is listed while it runs and cancelled when the app quits.
+    ///
+    /// # Arguments
+    ///
+    /// * `tasks` - The task manager of the app.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - An Ok result or an error.
+    fn register_task_handler(&mut self, tasks: Tasks) -> Result<()> {
+        let _ = tasks; // to appease clippy
+        Ok(())
+    }
+    /// Register the timers of the app, for sending an action after a delay, e.g. to hide a
+    /// message, or at an interval, e.g. to refresh data, more precisely than counting ticks.
+    ///
+    /// # Arguments
+    ///
+    /// * `timers` - The timers of the app.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<()>` - An Ok result or an error.
+    fn register_timer_handler(&mut self, timers: Timers) -> Result<()> {
+        let _ = timers; // to appease clippy
+        Ok(())
+    }
     /// Initialize the component with a specified area if necessary.
     ///
     /// # Arguments
@@ -68,12 +123,19 @@ pub trait Component {
         let action = match event {
             Some(Event::Key(key_event)) => self.handle_key_event(key_event)?,
             Some(Event::Mouse(mouse_event)) => self.handle_mouse_event(mouse_event)?,
+            Some(Event::Paste(text)) => self.handle_paste(text)?,
+            Some(Event::FocusGained) => self.handle_terminal_focus(true)?,
+            Some(Event::FocusLost) => self.handle_terminal_focus(false)?,
             _ => None,
         };
         Ok(action)
     }
     /// Handle key events and produce actions if necessary.
     ///
+    /// With keyboard enhancement enabled (see [`crate::tui::Tui::keyboard_enhancement`]), this also
+    /// receives key repeat and release events, and presses of modifier keys on their own, so check
+    /// `key.kind` before acting on a key.
+    ///
     /// # Arguments
     ///
     /// * `key` - A key event to be processed.
@@ -85,7 +147,23 @@ pub trait Component {
         let _ = key; // to appease clippy
         Ok(None)
     }
-    /// Handle mouse events and produce actions if necessary.
+    /// Handle text pasted into the terminal and produce actions if necessary. Pasted text arrives
+    /// as one event instead of a key event per character, and only goes to the focused component.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The pasted text.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        let _ = text; // to appease clippy
+        Ok(None)
+    }
+    /// Handle mouse events and produce actions if necessary. Mouse events only go to the
+    /// component under the cursor (see [`Component::hit_test`]), with the position relative to
+    /// the top left corner of the area the component was last drawn into.
     ///
     /// # Arguments
     ///
@@ -98,6 +176,191 @@ pub trait Component {
         let _ = mouse; // to appease clippy
         Ok(None)
     }
+    /// Whether the component is under the mouse cursor, and so receives mouse events. Components
+    /// that only draw into part of their area, like popups drawn over the whole screen, should
+    /// only claim that part, and only while it is shown.
+    ///
+    /// # Arguments
+    ///
+    /// * `area` - The area the component was last drawn into.
+    /// * `position` - The position of the mouse cursor.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - True if the component is under the mouse cursor.
+    fn hit_test(&self, area: Rect, position: Position) -> bool {
+        area.contains(position)
+    }
+    /// Handle the mouse cursor moving onto the component and produce actions if necessary.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_mouse_enter(&mut self) -> Result<Option<Action>> {
+        Ok(None)
+    }
+    /// Handle the mouse cursor moving off the component and produce actions if necessary.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_mouse_leave(&mut self) -> Result<Option<Action>> {
+        Ok(None)
+    }
+    /// Handle the terminal window gaining or losing focus and produce actions if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `focused` - True if the terminal gained focus, false if it lost it.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_terminal_focus(&mut self, focused: bool) -> Result<Option<Action>> {
+        let _ = focused; // to appease clippy
+        Ok(None)
+    }
+    /// Handle the terminal being resized and produce actions if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `area` - The area the component is drawn into from now on.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_resize(&mut self, area: Rect) -> Result<Option<Action>> {
+        let _ = area; // to appease clippy
+        Ok(None)
+    }
+    /// Whether the component can receive focus. Only the focused component receives key and paste
+    /// events.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - True if the component can be focused.
+    fn focusable(&self) -> bool {
+        true
+    }
+    /// Whether the component needs another frame drawn even though nothing else changed, e.g.
+    /// while it is animating. Frames are otherwise only drawn after an event or action is handled,
+    /// so return true for as long as the component should be drawn continuously.
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - True if a frame should be drawn.
+    fn needs_render(&self) -> bool {
+        false
+    }
+    /// Where the component wants the terminal cursor shown, e.g. at the insertion point of a text
+    /// field, as worked out by its last `draw`. This is only asked of the focused component, right
+    /// after it is drawn, and the cursor is hidden when it returns none.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Cursor>` - The position and shape of the cursor, or none to hide it.
+    fn cursor(&self) -> Option<Cursor> {
+        None
+    }
+    /// The links in the text drawn by the component's last `draw`. Terminals that support OSC 8
+    /// hyperlinks make the text in the area of each link clickable.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Hyperlink>` - The area and URL of each link.
+    fn hyperlinks(&self) -> Vec<Hyperlink> {
+        Vec::new()
+    }
+    /// Handle the component gaining focus and produce actions if necessary.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_focus_gained(&mut self) -> Result<Option<Action>> {
+        Ok(None)
+    }
+    /// Handle the component losing focus and produce actions if necessary.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_focus_lost(&mut self) -> Result<Option<Action>> {
+        Ok(None)
+    }
+    /// Handle a change to the keys of a partially typed key sequence and produce actions if
+    /// necessary. This can be used to show which keys have been typed and what they can lead to.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The keys typed so far, or empty once the sequence completes or is cancelled.
+    /// * `continuations` - The keys that would complete a binding, with the action each runs.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_pending_keys(
+        &mut self,
+        keys: &[KeyEvent],
+        continuations: &[(Vec<KeyEvent>, Action)],
+    ) -> Result<Option<Action>> {
+        let _ = (keys, continuations); // to appease clippy
+        Ok(None)
+    }
+    /// Handle the application entering a mode and produce actions if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The mode that is now active.
+    /// * `stack` - The mode stack, from the base mode to the active mode.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_mode_enter(&mut self, mode: Mode, stack: &[Mode]) -> Result<Option<Action>> {
+        let _ = (mode, stack); // to appease clippy
+        Ok(None)
+    }
+    /// Handle the application leaving a mode and produce actions if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The mode that is no longer active.
+    /// * `stack` - The mode stack, from the base mode to the newly active mode.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn handle_mode_exit(&mut self, mode: Mode, stack: &[Mode]) -> Result<Option<Action>> {
+        let _ = (mode, stack); // to appease clippy
+        Ok(None)
+    }
+    /// Undo a change the component recorded, and produce actions if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `change` - The change to undo. Use `change.data().downcast_ref()` to get the value that
+    ///   was recorded.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn undo(&mut self, change: &Change) -> Result<Option<Action>> {
+        let _ = change; // to appease clippy
+        Ok(None)
+    }
+    /// Redo a change the component recorded and that was undone, and produce actions if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `change` - The change to redo.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<Action>>` - An action to be processed or none.
+    fn redo(&mut self, change: &Change) -> Result<Option<Action>> {
+        let _ = change; // to appease clippy
+        Ok(None)
+    }
     /// Update the state of the component based on a received action. (REQUIRED)
     ///
     /// # Arguments
@@ -123,3 +386,14 @@ pub trait Component {
     /// * `Result<()>` - An Ok result or an error.
     fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()>;
 }
+
+/// A centered area taking up the given percentages of `area`, for drawing popups.
+pub fn popup_area(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Percentage(percent_y)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
+}
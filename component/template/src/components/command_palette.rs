@@ -0,0 +1,300 @@
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{popup_area, Component};
+use crate::{
+    action::Action,
+    config::Config,
+    mode::Mode,
+    tui::{Cursor, CursorShape},
+};
+
+/// The id the command palette must be registered with, so that it can focus itself when opened.
+pub const ID: &str = "command_palette";
+
+/// A popup that lists every action with the keys bound to it, and runs the action chosen by
+/// fuzzy searching for it.
+#[derive(Default)]
+pub struct CommandPalette {
+    command_tx: Option<UnboundedSender<Action>>,
+    config: Config,
+    open: bool,
+    /// The mode the palette was opened from, whose keybindings are listed.
+    mode: Mode,
+    query: String,
+    /// Every action with the keys bound to it in `mode`.
+    entries: Vec<(Action, Vec<String>)>,
+    /// The indices of the entries that match the query, best match first.
+    matches: Vec<usize>,
+    list_state: ListState,
+    /// The end of the query, where the cursor goes, as of the last draw.
+    cursor: Option<Cursor>,
+}
+
+impl CommandPalette {
+    fn send(&self, action: Action) -> Result<()> {
+        if let Some(tx) = &self.command_tx {
+            tx.send(action)?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> Result<()> {
+        if self.open {
+            return Ok(());
+        }
+        self.open = true;
+        self.query.clear();
+        self.entries = self.build_entries();
+        self.filter();
+        self.send(Action::PushMode(Mode::CommandPalette))?;
+        self.send(Action::Focus(ID.to_string()))
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.open = false;
+        self.send(Action::PopMode)
+    }
+
+    /// Every bound action with its keys, followed by every other command.
+    fn build_entries(&self) -> Vec<(Action, Vec<String>)> {
+        let mut entries = self.config.bindings_by_action(self.mode);
+        for action in Action::commands() {
+            if !entries.iter().any(|(other, _)| *other == action) {
+                entries.push((action, Vec::new()));
+            }
+        }
+        entries
+    }
+
+    fn filter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (action, _))| {
+                fuzzy_score(&self.query, &action.to_string()).map(|score| (score, index))
+            })
+            .collect();
+        // sort_by_key is stable, so equal scores keep the order of the entries
+        scored.sort_by_key(|(score, _)| -score);
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.list_state
+            .select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn selected(&self) -> Option<&Action> {
+        let index = self.matches.get(self.list_state.selected()?)?;
+        Some(&self.entries[*index].0)
+    }
+}
+
+impl Component for CommandPalette {
+    fn register_action_handler(&mut self, tx: UnboundedSender<Action>) -> Result<()> {
+        self.command_tx = Some(tx);
+        Ok(())
+    }
+
+    fn register_config_handler(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn focusable(&self) -> bool {
+        self.open
+    }
+
+    fn hit_test(&self, area: Rect, position: Position) -> bool {
+        self.open && popup_area(area, 60, 60).contains(position)
+    }
+
+    fn handle_mode_enter(&mut self, mode: Mode, _stack: &[Mode]) -> Result<Option<Action>> {
+        if mode != Mode::CommandPalette {
+            self.mode = mode;
+        }
+        Ok(None)
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent) -> Result<Option<Action>> {
+        if !self.open || key.kind == KeyEventKind::Release {
+            return Ok(None);
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => self.close()?,
+            KeyCode::Enter => {
+                let selected = self.selected().cloned();
+                self.close()?;
+                if let Some(action) = selected {
+                    self.send(action)?;
+                }
+            }
+            KeyCode::Up => self.list_state.select_previous(),
+            KeyCode::Down => self.list_state.select_next(),
+            KeyCode::Char('p') if ctrl => self.list_state.select_previous(),
+            KeyCode::Char('n') if ctrl => self.list_state.select_next(),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.filter();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn handle_paste(&mut self, text: String) -> Result<Option<Action>> {
+        if self.open {
+            self.query.extend(text.chars().filter(|c| !c.is_control()));
+            self.filter();
+        }
+        Ok(None)
+    }
+
+    fn cursor(&self) -> Option<Cursor> {
+        self.cursor
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        if action == Action::OpenCommandPalette {
+            self.open()?;
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        self.cursor = None;
+        if !self.open {
+            return Ok(());
+        }
+        let area = popup_area(area, 60, 60);
+        let block = Block::bordered().title("Command Palette");
+        let inner = block.inner(area);
+        let [input_area, list_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|index| {
+                let (action, keys) = &self.entries[*index];
+                ListItem::new(Line::from(vec![
+                    Span::raw(action.to_string()),
+                    Span::raw("  "),
+                    Span::styled(keys.join(", "), Style::new().dim()),
+                ]))
+            })
+            .collect();
+        let list = List::new(items).highlight_style(Style::new().reversed());
+        let input = Line::from(format!("> {}", self.query));
+        let input_width = u16::try_from(input.width()).unwrap_or(u16::MAX);
+        let x = input_area
+            .x
+            .saturating_add(input_width)
+            .min(input_area.right().saturating_sub(1));
+        self.cursor =
+            Some(Cursor::new(Position::new(x, input_area.y)).shape(CursorShape::BlinkingBar));
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+        frame.render_widget(Paragraph::new(input), input_area);
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+        Ok(())
+    }
+}
+
+/// Score how well `query` matches `candidate`, or `None` if the characters of the query don't all
+/// appear in the candidate in order. Matching ignores case, and higher scores are better:
+/// consecutive characters and characters at the start of a word score extra.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let offset = candidate[position..]
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(&q))?;
+        let index = position + offset;
+        score += 1;
+        if previous_match.is_some_and(|previous| previous + 1 == index) {
+            score += 5;
+        }
+        let starts_word = index == 0
+            || !candidate[index - 1].is_alphanumeric()
+            || (candidate[index].is_uppercase() && candidate[index - 1].is_lowercase());
+        if starts_word {
+            score += 3;
+        }
+        // prefer matches that skip fewer characters
+        score -= offset as i64;
+        previous_match = Some(index);
+        position = index + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score_matches_subsequence() {
+        assert!(fuzzy_score("cs", "ClearScreen").is_some());
+        assert!(fuzzy_score("quit", "Quit").is_some());
+        assert!(fuzzy_score("", "Quit").is_some());
+        assert_eq!(fuzzy_score("tiuq", "Quit"), None);
+        assert_eq!(fuzzy_score("x", "Quit"), None);
+    }
+
+    #[test]
+    fn test_fuzzy_score_prefers_word_starts_and_runs() {
+        let word_starts = fuzzy_score("cs", "ClearScreen").unwrap();
+        let middle = fuzzy_score("cs", "Focus").unwrap();
+        assert!(word_starts > middle);
+        let run = fuzzy_score("sus", "Suspend").unwrap();
+        let scattered = fuzzy_score("sus", "ScrollUpScreen").unwrap();
+        assert!(run > scattered);
+    }
+
+    #[test]
+    fn test_entries_include_bound_keys_and_commands() {
+        let palette = CommandPalette {
+            config: Config::new().unwrap(),
+            ..Default::default()
+        };
+        let entries = palette.build_entries();
+        let quit = entries
+            .iter()
+            .find(|(action, _)| *action == Action::Quit)
+            .unwrap();
+        assert_eq!(quit.1, vec!["<ctrl-c>", "<ctrl-d>", "<q>"]);
+        assert!(entries
+            .iter()
+            .any(|(action, keys)| *action == Action::ClearScreen && keys.is_empty()));
+        assert!(!entries.iter().any(|(action, _)| *action == Action::Tick));
+    }
+
+    #[test]
+    fn test_filter_selects_best_match() {
+        let mut palette = CommandPalette {
+            config: Config::new().unwrap(),
+            ..Default::default()
+        };
+        palette.entries = palette.build_entries();
+        palette.query = "clear".to_string();
+        palette.filter();
+        assert_eq!(palette.selected(), Some(&Action::ClearScreen));
+        palette.query = "zzz".to_string();
+        palette.filter();
+        assert_eq!(palette.selected(), None);
+    }
+}
@@ -0,0 +1,134 @@
+use color_eyre::Result;
+use ratatui::{prelude::*, widgets::*};
+
+use super::Component;
+use crate::{action::Action, osc::Progress, tasks::TaskId};
+
+/// The widest the jobs list is drawn.
+const WIDTH: u16 = 40;
+
+/// Lists the running background tasks and their progress in the bottom right corner, while there
+/// are any. See [`crate::tasks::Tasks`].
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+}
+
+struct Job {
+    id: TaskId,
+    name: String,
+    progress: Progress,
+}
+
+impl Job {
+    fn line(&self) -> Line<'static> {
+        let progress = match self.progress {
+            Progress::Hidden => Span::raw(""),
+            Progress::Normal(percent) => Span::raw(format!("{percent:>3}%")),
+            Progress::Error(percent) => Span::raw(format!("{percent:>3}%")).red(),
+            Progress::Paused(percent) => Span::raw(format!("{percent:>3}%")).yellow(),
+            Progress::Indeterminate => Span::raw("   …"),
+        };
+        Line::from(vec![
+            Span::styled(format!("{:>3} ", self.id), Style::new().dim()),
+            Span::raw(self.name.clone()),
+            Span::raw(" "),
+            progress,
+        ])
+    }
+}
+
+impl Component for Jobs {
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn hit_test(&self, _area: Rect, _position: Position) -> bool {
+        false
+    }
+
+    fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::TaskStarted(id, name) => self.jobs.push(Job {
+                id,
+                name,
+                progress: Progress::Hidden,
+            }),
+            Action::TaskProgress(id, progress) => {
+                if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                    job.progress = progress;
+                }
+            }
+            Action::TaskFinished(id) | Action::TaskFailed(id, _) | Action::TaskCancelled(id) => {
+                self.jobs.retain(|job| job.id != id)
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
+        if self.jobs.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<Line> = self.jobs.iter().map(Job::line).collect();
+        let height = (lines.len() as u16).saturating_add(2).min(area.height);
+        let [_, popup] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(height)]).areas(area);
+        let [_, popup] =
+            Layout::horizontal([Constraint::Min(0), Constraint::Length(WIDTH)]).areas(popup);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Jobs")),
+            popup,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    fn screen(jobs: &mut Jobs) -> Result<Vec<String>> {
+        let mut terminal = Terminal::new(TestBackend::new(44, 5))?;
+        terminal.draw(|frame| {
+            jobs.draw(frame, frame.area()).unwrap();
+        })?;
+        let buffer = terminal.backend().buffer();
+        Ok((0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_lists_running_tasks() -> Result<()> {
+        let mut jobs = Jobs::default();
+        jobs.update(Action::TaskStarted(1, "Fetch".to_string()))?;
+        jobs.update(Action::TaskStarted(2, "Index".to_string()))?;
+        jobs.update(Action::TaskProgress(2, Progress::Normal(40)))?;
+        assert_eq!(
+            screen(&mut jobs)?,
+            vec![
+                "",
+                "    ┌Jobs──────────────────────────────────┐",
+                "    │  1 Fetch                             │",
+                "    │  2 Index  40%                        │",
+                "    └──────────────────────────────────────┘",
+            ]
+        );
+        jobs.update(Action::TaskFinished(1))?;
+        jobs.update(Action::TaskCancelled(2))?;
+        assert!(screen(&mut jobs)?.iter().all(String::is_empty));
+        Ok(())
+    }
+}
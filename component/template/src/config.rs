@@ -1,6 +1,6 @@
 #![allow(dead_code)] // Remove this once you start using the code
 
-use std::{collections::HashMap, env, path::PathBuf};
+use std::{collections::HashMap, env, path::PathBuf, time::Duration};
 
 use color_eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
@@ -11,7 +11,14 @@ use ratatui::style::{Color, Modifier, Style};
 use serde::{de::Deserializer, Deserialize};
 use tracing::error;
 
-use crate::{action::Action, app::Mode};
+use crate::{
+    action::Action,
+    capabilities::{Background, Capabilities, ColorSupport},
+    keymap::Keymap,
+    layout::LayoutNode,
+    mode::Mode,
+    tui::ViewportMode,
+};
 
 const CONFIG: &str = include_str!("../.config/config.json5");
 
@@ -29,8 +36,106 @@ pub struct Config {
     pub config: AppConfig,
     #[serde(default)]
     pub keybindings: KeyBindings,
+    /// Keybindings that only apply while the component with the given id has focus. These take
+    /// precedence over `keybindings`.
+    #[serde(default)]
+    pub component_keybindings: HashMap<String, KeyBindings>,
+    #[serde(default)]
+    pub keymap: KeymapConfig,
     #[serde(default)]
     pub styles: Styles,
+    #[serde(default)]
+    pub layout: Option<LayoutNode>,
+    /// Where in the terminal the app is drawn.
+    #[serde(default)]
+    pub viewport: Option<ViewportMode>,
+    /// Whether to capture the mouse, so components can be clicked, scrolled and hovered.
+    #[serde(default)]
+    pub mouse: Option<bool>,
+    /// Whether to enable bracketed paste, so pasted text arrives as one event instead of a key
+    /// event per character.
+    #[serde(default)]
+    pub paste: Option<bool>,
+    #[serde(default)]
+    pub session: SessionConfig,
+    /// What the terminal supports, e.g. to pick colours that suit a light or dark background.
+    #[serde(skip)]
+    pub capabilities: Capabilities,
+}
+
+/// Settings for resolving multi-key sequences such as `<g><g>`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct KeymapConfig {
+    /// How long to wait for the next key of a sequence, in milliseconds. When a sequence is both
+    /// bound and the prefix of a longer binding (e.g. `<g>` and `<g><g>`), the shorter binding
+    /// runs once the timeout expires.
+    #[serde(default = "default_sequence_timeout")]
+    pub timeout_ms: u64,
+    /// The key that cancels a partially typed sequence.
+    #[serde(
+        default = "default_cancel_key",
+        deserialize_with = "deserialize_key_event"
+    )]
+    pub cancel: KeyEvent,
+}
+
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: default_sequence_timeout(),
+            cancel: default_cancel_key(),
+        }
+    }
+}
+
+impl KeymapConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+fn default_sequence_timeout() -> u64 {
+    1000
+}
+
+fn default_cancel_key() -> KeyEvent {
+    KeyEvent::new(KeyCode::Esc, KeyModifiers::empty())
+}
+
+/// Settings for clients attached to an app run with `--serve`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SessionConfig {
+    /// The key that detaches the client, leaving the app running.
+    #[serde(
+        default = "default_detach_key",
+        deserialize_with = "deserialize_key_event"
+    )]
+    pub detach: KeyEvent,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            detach: default_detach_key(),
+        }
+    }
+}
+
+fn default_detach_key() -> KeyEvent {
+    KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL)
+}
+
+fn deserialize_key_event<'de, D>(deserializer: D) -> Result<KeyEvent, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = String::deserialize(deserializer)?;
+    match parse_key_sequence(&raw).map_err(serde::de::Error::custom)?[..] {
+        [key] => Ok(key),
+        _ => Err(serde::de::Error::custom(format!(
+            "Expected a single key, found `{raw}`"
+        ))),
+    }
 }
 
 lazy_static! {
@@ -77,13 +182,12 @@ impl Config {
 
         let mut cfg: Self = builder.build()?.try_deserialize()?;
 
-        for (mode, default_bindings) in default_config.keybindings.iter() {
-            let user_bindings = cfg.keybindings.entry(*mode).or_default();
-            for (key, cmd) in default_bindings.iter() {
-                user_bindings
-                    .entry(key.clone())
-                    .or_insert_with(|| cmd.clone());
-            }
+        cfg.keybindings.merge_defaults(&default_config.keybindings);
+        for (id, default_bindings) in default_config.component_keybindings.iter() {
+            cfg.component_keybindings
+                .entry(id.clone())
+                .or_default()
+                .merge_defaults(default_bindings);
         }
         for (mode, default_styles) in default_config.styles.iter() {
             let user_styles = cfg.styles.entry(*mode).or_default();
@@ -91,9 +195,40 @@ impl Config {
                 user_styles.entry(style_key.clone()).or_insert(*style);
             }
         }
+        if cfg.layout.is_none() {
+            cfg.layout = default_config.layout;
+        }
+        if cfg.viewport.is_none() {
+            cfg.viewport = default_config.viewport;
+        }
+        if cfg.mouse.is_none() {
+            cfg.mouse = default_config.mouse;
+        }
+        if cfg.paste.is_none() {
+            cfg.paste = default_config.paste;
+        }
 
         Ok(cfg)
     }
+
+    /// Build the keymap for `mode`, where the bindings of the focused component take precedence
+    /// over the bindings for the mode.
+    pub fn keymap(&self, mode: Mode, focus: Option<&str>) -> Keymap {
+        let mut keymap = Keymap::default();
+        let component_bindings = focus
+            .and_then(|id| self.component_keybindings.get(id))
+            .and_then(|keybindings| keybindings.get(&mode));
+        let bindings = self.keybindings.get(&mode);
+        for (keys, action) in bindings.into_iter().chain(component_bindings).flatten() {
+            keymap.insert(keys, action.clone());
+        }
+        keymap
+    }
+
+    /// Every action bound in `mode` with the key sequences bound to it, sorted by action.
+    pub fn bindings_by_action(&self, mode: Mode) -> Vec<(Action, Vec<String>)> {
+        self.keybindings.by_action(mode)
+    }
 }
 
 pub fn get_data_dir() -> PathBuf {
@@ -125,6 +260,37 @@ fn project_directory() -> Option<ProjectDirs> {
 #[derive(Clone, Debug, Default, Deref, DerefMut)]
 pub struct KeyBindings(pub HashMap<Mode, HashMap<Vec<KeyEvent>, Action>>);
 
+impl KeyBindings {
+    /// Every action bound in `mode` with the key sequences bound to it, sorted by action.
+    pub fn by_action(&self, mode: Mode) -> Vec<(Action, Vec<String>)> {
+        let mut bindings: Vec<(Action, Vec<String>)> = Vec::new();
+        for (keys, action) in self.get(&mode).into_iter().flatten() {
+            let keys = key_sequence_to_string(keys);
+            match bindings.iter_mut().find(|(other, _)| other == action) {
+                Some((_, bound)) => bound.push(keys),
+                None => bindings.push((action.clone(), vec![keys])),
+            }
+        }
+        for (_, keys) in bindings.iter_mut() {
+            keys.sort();
+        }
+        bindings.sort_by_cached_key(|(action, _)| action.to_string());
+        bindings
+    }
+
+    /// Add the bindings from `defaults` for any key sequence that isn't already bound.
+    fn merge_defaults(&mut self, defaults: &KeyBindings) {
+        for (mode, default_bindings) in defaults.iter() {
+            let user_bindings = self.entry(*mode).or_default();
+            for (key, cmd) in default_bindings.iter() {
+                user_bindings
+                    .entry(key.clone())
+                    .or_insert_with(|| cmd.clone());
+            }
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for KeyBindings {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -137,11 +303,12 @@ impl<'de> Deserialize<'de> for KeyBindings {
             .map(|(mode, inner_map)| {
                 let converted_inner_map = inner_map
                     .into_iter()
-                    .map(|(key_str, cmd)| (parse_key_sequence(&key_str).unwrap(), cmd))
-                    .collect();
-                (mode, converted_inner_map)
+                    .map(|(key_str, cmd)| Ok((parse_key_sequence(&key_str)?, cmd)))
+                    .collect::<Result<_, String>>()?;
+                Ok((mode, converted_inner_map))
             })
-            .collect();
+            .collect::<Result<_, String>>()
+            .map_err(serde::de::Error::custom)?;
 
         Ok(KeyBindings(keybindings))
     }
@@ -216,7 +383,7 @@ fn parse_key_code_with_modifiers(
         "hyphen" => KeyCode::Char('-'),
         "minus" => KeyCode::Char('-'),
         "tab" => KeyCode::Tab,
-        c if c.len() == 1 => {
+        c if c.chars().count() == 1 => {
             let mut c = c.chars().next().unwrap();
             if modifiers.contains(KeyModifiers::SHIFT) {
                 c = c.to_ascii_uppercase();
@@ -291,6 +458,13 @@ pub fn key_event_to_string(key_event: &KeyEvent) -> String {
     key
 }
 
+/// Format a key sequence in the form accepted by [`parse_key_sequence`], e.g. `<ctrl-a><b>`.
+pub fn key_sequence_to_string(keys: &[KeyEvent]) -> String {
+    keys.iter()
+        .map(|key| format!("<{}>", key_event_to_string(key)))
+        .collect()
+}
+
 pub fn parse_key_sequence(raw: &str) -> Result<Vec<KeyEvent>, String> {
     if raw.chars().filter(|c| *c == '>').count() != raw.chars().filter(|c| *c == '<').count() {
         return Err(format!("Unable to parse `{}`", raw));
@@ -321,6 +495,41 @@ pub fn parse_key_sequence(raw: &str) -> Result<Vec<KeyEvent>, String> {
 #[derive(Clone, Debug, Default, Deref, DerefMut)]
 pub struct Styles(pub HashMap<Mode, HashMap<String, Style>>);
 
+impl Styles {
+    /// The built-in styles that suit `background`, assuming a dark one if it's unknown.
+    fn defaults(background: Option<Background>) -> Self {
+        let (title, selected) = match background {
+            Some(Background::Light) => (Color::Blue, Color::Gray),
+            Some(Background::Dark) | None => (Color::LightCyan, Color::DarkGray),
+        };
+        let home = HashMap::from([
+            (
+                "title".to_string(),
+                Style::new().fg(title).add_modifier(Modifier::BOLD),
+            ),
+            ("selected".to_string(), Style::new().bg(selected)),
+        ]);
+        Styles(HashMap::from([(Mode::Home, home)]))
+    }
+
+    /// Add the built-in styles for `background` that the config doesn't set.
+    pub fn fill_defaults(&mut self, background: Option<Background>) {
+        for (mode, defaults) in Self::defaults(background).0 {
+            let styles = self.entry(mode).or_default();
+            for (key, style) in defaults {
+                styles.entry(key).or_insert(style);
+            }
+        }
+    }
+
+    /// Downgrade the colours of every style to those the terminal can show.
+    pub fn downgrade(&mut self, colors: ColorSupport) {
+        for style in self.values_mut().flat_map(HashMap::values_mut) {
+            *style = colors.downgrade_style(*style);
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for Styles {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -385,7 +594,12 @@ fn process_color_string(color_str: &str) -> (String, Modifier) {
 fn parse_color(s: &str) -> Option<Color> {
     let s = s.trim_start();
     let s = s.trim_end();
-    if s.contains("bright color") {
+    if let Some(hex) = s.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)?;
+        Some(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+    } else if s.contains("bright color") {
         let s = s.trim_start_matches("bright ");
         let c = s
             .trim_start_matches("color")
@@ -452,6 +666,7 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
+    use crate::keymap::Lookup;
 
     #[test]
     fn test_parse_style_default() {
@@ -494,6 +709,54 @@ mod tests {
         assert_eq!(color, Some(Color::Indexed(expected)));
     }
 
+    #[test]
+    fn test_parse_color_hex() {
+        assert_eq!(parse_color("#ff8000"), Some(Color::Rgb(255, 128, 0)));
+        assert_eq!(
+            parse_style("#000000 on #ffffff").bg,
+            Some(Color::Rgb(255, 255, 255))
+        );
+        assert_eq!(parse_color("#fff"), None);
+    }
+
+    #[test]
+    fn test_styles_fill_defaults_for_background() {
+        let mut light = Styles::default();
+        light
+            .entry(Mode::Home)
+            .or_default()
+            .insert("title".to_string(), Style::new().fg(Color::Red));
+        light.fill_defaults(Some(Background::Light));
+        assert_eq!(light[&Mode::Home]["title"].fg, Some(Color::Red));
+        assert_eq!(light[&Mode::Home]["selected"].bg, Some(Color::Gray));
+
+        let mut dark = Styles::default();
+        dark.fill_defaults(Some(Background::Dark));
+        assert_eq!(dark[&Mode::Home]["title"].fg, Some(Color::LightCyan));
+        assert_eq!(dark[&Mode::Home]["selected"].bg, Some(Color::DarkGray));
+
+        let mut unknown = Styles::default();
+        unknown.fill_defaults(None);
+        assert_eq!(
+            unknown[&Mode::Home]["selected"],
+            dark[&Mode::Home]["selected"]
+        );
+    }
+
+    #[test]
+    fn test_styles_downgrade() {
+        let mut styles = Styles::default();
+        styles
+            .entry(Mode::Home)
+            .or_default()
+            .insert("title".to_string(), parse_style("bold #ff0000 on blue"));
+        styles.downgrade(ColorSupport::Ansi256);
+        let style = styles[&Mode::Home]["title"];
+        assert_eq!(style.fg, Some(Color::Indexed(196)));
+        assert_eq!(style.bg, Some(Color::Indexed(4)));
+        assert!(style.add_modifier.contains(Modifier::BOLD));
+    }
+
     #[test]
     fn test_parse_color_unknown() {
         let color = parse_color("unknown");
@@ -511,9 +774,103 @@ mod tests {
                 .unwrap(),
             &Action::Quit
         );
+        assert_eq!(c.mouse, Some(false));
+        assert_eq!(c.paste, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_component_keybindings_take_precedence() -> Result<()> {
+        let mut c = Config::new()?;
+        let keys = parse_key_sequence("<q>").unwrap();
+        let mut bindings = HashMap::new();
+        bindings.insert(keys.clone(), Action::Suspend);
+        c.component_keybindings
+            .entry("home".to_string())
+            .or_default()
+            .insert(Mode::Home, bindings);
+        assert_eq!(
+            c.keymap(Mode::Home, Some("home")).lookup(&keys),
+            Lookup::Matched(Action::Suspend)
+        );
+        assert_eq!(
+            c.keymap(Mode::Home, Some("fps")).lookup(&keys),
+            Lookup::Matched(Action::Quit)
+        );
+        assert_eq!(
+            c.keymap(Mode::Home, None).lookup(&keys),
+            Lookup::Matched(Action::Quit)
+        );
         Ok(())
     }
 
+    #[test]
+    fn test_bindings_by_action() -> Result<()> {
+        let c = Config::new()?;
+        let bindings = c.bindings_by_action(Mode::Home);
+        let (_, keys) = bindings
+            .iter()
+            .find(|(action, _)| *action == Action::Quit)
+            .unwrap();
+        assert_eq!(keys, &vec!["<ctrl-c>", "<ctrl-d>", "<q>"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_keymap_config() {
+        let c: KeymapConfig = json5::from_str(r#"{ "cancel": "<ctrl-g>" }"#).unwrap();
+        assert_eq!(c.timeout(), Duration::from_secs(1));
+        assert_eq!(
+            c.cancel,
+            KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL)
+        );
+        assert!(json5::from_str::<KeymapConfig>(r#"{ "cancel": "<g><g>" }"#).is_err());
+    }
+
+    #[test]
+    fn test_session_config() {
+        let default_config: Config = json5::from_str(CONFIG).unwrap();
+        let c: SessionConfig = json5::from_str("{}").unwrap();
+        assert_eq!(c.detach, default_config.session.detach);
+        assert_eq!(
+            c.detach,
+            KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL)
+        );
+    }
+
+    #[test]
+    fn test_key_sequence_round_trip() {
+        let keys = parse_key_sequence("<ctrl-a><b>").unwrap();
+        assert_eq!(key_sequence_to_string(&keys), "<ctrl-a><b>");
+        assert_eq!(parse_key_sequence(&key_sequence_to_string(&keys)), Ok(keys));
+    }
+
+    #[test]
+    fn test_parameterised_keybindings() {
+        let keybindings: KeyBindings =
+            json5::from_str(r#"{ "Home": { "<j>": "ScrollDown 5", "<k>": { "ScrollUp": 5 } } }"#)
+                .unwrap();
+        let keymap = &keybindings[&Mode::Home];
+        assert_eq!(
+            keymap[&parse_key_sequence("<j>").unwrap()],
+            Action::ScrollDown(5)
+        );
+        assert_eq!(
+            keymap[&parse_key_sequence("<k>").unwrap()],
+            Action::ScrollUp(5)
+        );
+    }
+
+    #[test]
+    fn test_invalid_keybindings() {
+        let err = json5::from_str::<KeyBindings>(r#"{ "Home": { "<j>": "ScrollDown lots" } }"#)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid argument `lots` for `ScrollDown`"));
+        assert!(json5::from_str::<KeyBindings>(r#"{ "Home": { "<nope>": "Quit" } }"#).is_err());
+    }
+
     #[test]
     fn test_simple_keys() {
         assert_eq!(
@@ -566,6 +923,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_keys_only_distinct_with_keyboard_enhancement() -> Result<()> {
+        // legacy terminals send the same codes for these, so the bindings only differ with
+        // keyboard enhancement enabled, which reports the keys as they were pressed
+        let config = Config {
+            keybindings: json5::from_str(
+                r#"{ "Home": {
+                    "<Ctrl-i>": "FocusNext", "<Tab>": "FocusPrevious",
+                    "<Ctrl-m>": "Help", "<Enter>": "Quit",
+                    "<Ctrl-[>": "Undo", "<Esc>": "Redo",
+                } }"#,
+            )?,
+            ..Config::default()
+        };
+        let keymap = config.keymap(Mode::Home, None);
+        let lookup = |code, modifiers| keymap.lookup(&[KeyEvent::new(code, modifiers)]);
+        let ctrl = KeyModifiers::CONTROL;
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            lookup(KeyCode::Char('i'), ctrl),
+            Lookup::Matched(Action::FocusNext)
+        );
+        assert_eq!(
+            lookup(KeyCode::Tab, none),
+            Lookup::Matched(Action::FocusPrevious)
+        );
+        assert_eq!(
+            lookup(KeyCode::Char('m'), ctrl),
+            Lookup::Matched(Action::Help)
+        );
+        assert_eq!(lookup(KeyCode::Enter, none), Lookup::Matched(Action::Quit));
+        assert_eq!(
+            lookup(KeyCode::Char('['), ctrl),
+            Lookup::Matched(Action::Undo)
+        );
+        assert_eq!(lookup(KeyCode::Esc, none), Lookup::Matched(Action::Redo));
+        Ok(())
+    }
+
     #[test]
     fn test_reverse_multiple_modifiers() {
         assert_eq!(
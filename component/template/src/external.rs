@@ -0,0 +1,151 @@
+use std::{
+    env,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use tokio::{fs, io::AsyncWriteExt, process::Command};
+
+use crate::config::PROJECT_NAME;
+
+/// How many paths to try for the temporary file before giving up, if files already exist at them.
+const TEMP_FILE_ATTEMPTS: usize = 100;
+
+/// A request to edit text with an external command, such as the user's editor, while the app is
+/// suspended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    /// The id of the component the edited text is sent back to.
+    pub component: String,
+    /// The command to run, with the path of a file containing the text added as its last argument.
+    pub command: String,
+    /// The text to start with.
+    pub text: String,
+}
+
+impl Edit {
+    /// Write the text to a temporary file, run the command on it and read the edited text back.
+    pub async fn run(&self) -> Result<String> {
+        let path = create_temp_file(&self.text).await?;
+        let text = match shell(&self.command, &path).status().await {
+            Ok(status) if status.success() => fs::read_to_string(&path).await.map_err(Into::into),
+            Ok(status) => Err(eyre!("`{}` failed with {status}", self.command)),
+            Err(err) => Err(eyre!("Unable to run `{}`: {err}", self.command)),
+        };
+        let _ = fs::remove_file(&path).await;
+        text
+    }
+}
+
+/// The user's preferred editor, from `$VISUAL` or `$EDITOR`.
+pub fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+/// Create a temporary file containing `text` that only the user can read and write. The file must
+/// not exist yet, so that another user can't have planted a link at its path in the shared temp
+/// directory to redirect the text.
+async fn create_temp_file(text: &str) -> Result<PathBuf> {
+    for _ in 0..TEMP_FILE_ATTEMPTS {
+        let path = temp_path();
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = match options.open(&path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let written = async {
+            file.write_all(text.as_bytes()).await?;
+            file.flush().await
+        };
+        if let Err(err) = written.await {
+            let _ = fs::remove_file(&path).await;
+            return Err(err.into());
+        }
+        return Ok(path);
+    }
+    Err(eyre!("Unable to create a temporary file to edit"))
+}
+
+/// A path for a temporary file to edit, which differs each time it is called.
+fn temp_path() -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let count = COUNT.fetch_add(1, Ordering::Relaxed);
+    let name = format!(
+        "{}-{}-{count}.txt",
+        PROJECT_NAME.to_lowercase(),
+        std::process::id()
+    );
+    env::temp_dir().join(name)
+}
+
+/// Run `command` through the shell, so that commands like `code --wait` work, with `path` as its
+/// last argument.
+fn shell(command: &str, path: &Path) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(format!("{command} \"$1\"")).arg("sh");
+        shell
+    };
+    shell.arg(path);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn edit(command: &str, text: &str) -> Edit {
+        Edit {
+            component: "home".to_string(),
+            command: command.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_edit_returns_edited_text() -> Result<()> {
+        let text = edit("printf ' world' >>", "hello").run().await?;
+        assert_eq!(text, "hello world");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_edit_fails_when_command_fails() {
+        assert!(edit("false", "hello").run().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_temp_file_is_private_and_new() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = create_temp_file("hello").await?;
+        let mode = fs::metadata(&path).await?.permissions().mode();
+        let text = fs::read_to_string(&path).await?;
+        fs::remove_file(&path).await?;
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(text, "hello");
+        Ok(())
+    }
+}
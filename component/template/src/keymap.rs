@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyEvent;
+
+use crate::{action::Action, config::key_event_to_string};
+
+/// A prefix tree of key sequences and the actions they are bound to.
+///
+/// The tree makes it cheap to tell whether the keys pressed so far are a complete binding, the
+/// start of a longer binding, both (e.g. `<g>` and `<g><g>` are both bound), or neither.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Keymap {
+    root: Node,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Node {
+    action: Option<Action>,
+    children: HashMap<KeyEvent, Node>,
+}
+
+/// The result of looking up a key sequence in a [`Keymap`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lookup {
+    /// The sequence is bound to an action and is not the prefix of any longer binding.
+    Matched(Action),
+    /// The sequence is the prefix of longer bindings. If the sequence is itself bound, the action
+    /// is included so it can be run when no further keys arrive.
+    Pending(Option<Action>),
+    /// No binding starts with the sequence.
+    Unmatched,
+}
+
+impl Keymap {
+    /// Bind `keys` to `action`, replacing any existing binding for the same sequence.
+    pub fn insert(&mut self, keys: &[KeyEvent], action: Action) {
+        let mut node = &mut self.root;
+        for key in keys {
+            node = node.children.entry(*key).or_default();
+        }
+        node.action = Some(action);
+    }
+
+    pub fn lookup(&self, keys: &[KeyEvent]) -> Lookup {
+        match self.node(keys) {
+            None => Lookup::Unmatched,
+            Some(node) if node.children.is_empty() => match &node.action {
+                Some(action) => Lookup::Matched(action.clone()),
+                None => Lookup::Unmatched,
+            },
+            Some(node) => Lookup::Pending(node.action.clone()),
+        }
+    }
+
+    /// Every binding that starts with `prefix`, as the keys that complete the sequence and the
+    /// action they are bound to, sorted by the remaining keys.
+    pub fn continuations(&self, prefix: &[KeyEvent]) -> Vec<(Vec<KeyEvent>, Action)> {
+        let mut continuations = Vec::new();
+        if let Some(node) = self.node(prefix) {
+            node.collect(&mut Vec::new(), &mut continuations);
+        }
+        continuations.sort_by_cached_key(|(keys, _)| {
+            keys.iter().map(key_event_to_string).collect::<Vec<_>>()
+        });
+        continuations
+    }
+
+    fn node(&self, keys: &[KeyEvent]) -> Option<&Node> {
+        keys.iter()
+            .try_fold(&self.root, |node, key| node.children.get(key))
+    }
+}
+
+impl Node {
+    fn collect(&self, keys: &mut Vec<KeyEvent>, out: &mut Vec<(Vec<KeyEvent>, Action)>) {
+        for (key, child) in &self.children {
+            keys.push(*key);
+            if let Some(action) = &child.action {
+                out.push((keys.clone(), action.clone()));
+            }
+            child.collect(keys, out);
+            keys.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::config::parse_key_sequence;
+
+    fn keys(raw: &str) -> Vec<KeyEvent> {
+        parse_key_sequence(raw).unwrap()
+    }
+
+    fn keymap() -> Keymap {
+        let mut keymap = Keymap::default();
+        keymap.insert(&keys("<q>"), Action::Quit);
+        keymap.insert(&keys("<g>"), Action::Help);
+        keymap.insert(&keys("<g><g>"), Action::ClearScreen);
+        keymap.insert(&keys("<z><z>"), Action::Suspend);
+        keymap
+    }
+
+    #[test]
+    fn test_lookup_single_key() {
+        assert_eq!(keymap().lookup(&keys("<q>")), Lookup::Matched(Action::Quit));
+    }
+
+    #[test]
+    fn test_lookup_sequence() {
+        let keymap = keymap();
+        assert_eq!(keymap.lookup(&keys("<z>")), Lookup::Pending(None));
+        assert_eq!(
+            keymap.lookup(&keys("<z><z>")),
+            Lookup::Matched(Action::Suspend)
+        );
+    }
+
+    #[test]
+    fn test_lookup_ambiguous_prefix() {
+        let keymap = keymap();
+        assert_eq!(
+            keymap.lookup(&keys("<g>")),
+            Lookup::Pending(Some(Action::Help))
+        );
+        assert_eq!(
+            keymap.lookup(&keys("<g><g>")),
+            Lookup::Matched(Action::ClearScreen)
+        );
+    }
+
+    #[test]
+    fn test_lookup_unmatched() {
+        let keymap = keymap();
+        assert_eq!(keymap.lookup(&keys("<x>")), Lookup::Unmatched);
+        assert_eq!(keymap.lookup(&keys("<z><x>")), Lookup::Unmatched);
+        assert_eq!(Keymap::default().lookup(&[]), Lookup::Unmatched);
+    }
+
+    #[test]
+    fn test_insert_replaces_binding() {
+        let mut keymap = keymap();
+        keymap.insert(&keys("<q>"), Action::Suspend);
+        assert_eq!(
+            keymap.lookup(&keys("<q>")),
+            Lookup::Matched(Action::Suspend)
+        );
+    }
+
+    #[test]
+    fn test_continuations() {
+        let keymap = keymap();
+        assert_eq!(
+            keymap.continuations(&keys("<g>")),
+            vec![(keys("<g>"), Action::ClearScreen)]
+        );
+        assert_eq!(
+            keymap.continuations(&keys("<z>")),
+            vec![(keys("<z>"), Action::Suspend)]
+        );
+        assert!(keymap.continuations(&keys("<x>")).is_empty());
+    }
+}
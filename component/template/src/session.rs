@@ -0,0 +1,730 @@
+//! Detachable sessions: the app runs as a server without a terminal of its own, and clients
+//! attach to it over a Unix socket, like tmux.
+//!
+//! A client forwards the input events of its terminal to the server, and the server sends back
+//! the cells of each frame. Frames are drawn at the smallest width and height of the attached
+//! clients, so that every client sees all of the frame; clients with larger terminals leave the
+//! rest blank. Cells are sent in full colour, and each client downgrades them to what its own
+//! terminal supports. Both directions are newline delimited JSON.
+//!
+//! Only the user running the server may attach: the socket is private to them, and clients of
+//! other users are turned away.
+
+use std::{
+    fs::{self, Permissions},
+    io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use color_eyre::{eyre::eyre, Result};
+use crossterm::event::KeyEvent;
+use futures::{stream, stream::BoxStream, StreamExt};
+use ratatui::{
+    backend::{Backend, WindowSize},
+    buffer::{Buffer, Cell},
+    layout::{Position, Rect, Size},
+    style::Style,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc::{self, Receiver, Sender, UnboundedSender},
+    task::JoinHandle,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    capabilities::{Capabilities, ColorSupport},
+    tui::{
+        CursorShape, Event, EventSource, Features, Tui, TuiBackend, ViewportMode, EVENT_CAPACITY,
+    },
+};
+
+/// A message from the server to its clients.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    /// Cells that changed since the last frame.
+    Draw(Vec<CellUpdate>),
+    Clear,
+    HideCursor,
+    ShowCursor,
+    SetCursor(u16, u16),
+    SetCursorShape(CursorShape),
+    /// An escape sequence to write straight to the terminal, e.g. the bell.
+    Escape(String),
+    /// Start or stop capturing the mouse.
+    Mouse(bool),
+    /// The server is done with the client, e.g. because the app quit.
+    Detach,
+}
+
+/// The new content of the cell at `x`, `y`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CellUpdate {
+    pub x: u16,
+    pub y: u16,
+    pub symbol: String,
+    pub style: Style,
+}
+
+impl CellUpdate {
+    fn new(x: u16, y: u16, cell: &Cell) -> Self {
+        Self {
+            x,
+            y,
+            symbol: cell.symbol().to_string(),
+            style: cell.style(),
+        }
+    }
+
+    /// The cell, with its colours downgraded to `colors`.
+    fn to_cell(&self, colors: ColorSupport) -> Cell {
+        let mut cell = Cell::default();
+        cell.set_symbol(&self.symbol)
+            .set_style(colors.downgrade_style(self.style));
+        cell
+    }
+}
+
+/// A client attached to the server.
+struct Client {
+    id: u64,
+    messages: UnboundedSender<ServerMessage>,
+    /// The size of the client's terminal, once it has sent it.
+    size: Option<Size>,
+}
+
+/// What the server has drawn, so that clients attaching later start with the current screen.
+struct Shared {
+    size: Size,
+    screen: Buffer,
+    cursor: Position,
+    cursor_visible: bool,
+    cursor_shape: CursorShape,
+    mouse: bool,
+    clients: Vec<Client>,
+    next_id: u64,
+}
+
+impl Shared {
+    fn new() -> Self {
+        let size = Size::new(80, 24);
+        Self {
+            size,
+            screen: Buffer::empty(Rect::from((Position::ORIGIN, size))),
+            cursor: Position::ORIGIN,
+            cursor_visible: false,
+            cursor_shape: CursorShape::Default,
+            mouse: false,
+            clients: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Send `message` to every client, forgetting the ones that have gone.
+    fn broadcast(&mut self, message: ServerMessage) {
+        self.clients
+            .retain(|client| client.messages.send(message.clone()).is_ok());
+    }
+
+    /// Add a client, sending it the current screen first, and return its id.
+    fn attach(&mut self, messages: UnboundedSender<ServerMessage>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let cells = self
+            .screen
+            .content
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let (x, y) = self.screen.pos_of(i);
+                CellUpdate::new(x, y, cell)
+            })
+            .collect();
+        let cursor = if self.cursor_visible {
+            ServerMessage::ShowCursor
+        } else {
+            ServerMessage::HideCursor
+        };
+        let snapshot = [
+            ServerMessage::Clear,
+            ServerMessage::Draw(cells),
+            ServerMessage::SetCursor(self.cursor.x, self.cursor.y),
+            cursor,
+            ServerMessage::SetCursorShape(self.cursor_shape),
+            ServerMessage::Mouse(self.mouse),
+        ];
+        if snapshot
+            .into_iter()
+            .all(|message| messages.send(message).is_ok())
+        {
+            self.clients.push(Client {
+                id,
+                messages,
+                size: None,
+            });
+        }
+        id
+    }
+
+    /// Forget the client with `id`, returning the new frame size if it changed.
+    fn detach(&mut self, id: u64) -> Option<Size> {
+        self.clients.retain(|client| client.id != id);
+        self.fit()
+    }
+
+    /// Record the terminal size of the client with `id`, returning the frame size.
+    fn resize_client(&mut self, id: u64, size: Size) -> Size {
+        if let Some(client) = self.clients.iter_mut().find(|client| client.id == id) {
+            client.size = Some(size);
+        }
+        self.fit();
+        self.size
+    }
+
+    /// Fit the frame to the smallest width and height of the clients, returning the new size if it
+    /// changed. The size is kept while no client has sent its size.
+    fn fit(&mut self) -> Option<Size> {
+        let sizes = self.clients.iter().filter_map(|client| client.size);
+        let width = sizes.clone().map(|size| size.width).min()?;
+        let height = sizes.map(|size| size.height).min()?;
+        let size = Size::new(width, height);
+        if size == self.size {
+            return None;
+        }
+        self.size = size;
+        self.screen.resize(Rect::from((Position::ORIGIN, size)));
+        Some(size)
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A backend that draws to the clients attached to a Unix socket instead of a terminal.
+///
+/// Suspending the app detaches the clients and keeps the server running.
+pub struct SessionBackend {
+    shared: Arc<Mutex<Shared>>,
+    socket: PathBuf,
+    /// The task accepting clients.
+    task: JoinHandle<()>,
+}
+
+impl Backend for SessionBackend {
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>,
+    {
+        let mut shared = lock(&self.shared);
+        let mut cells = Vec::new();
+        for (x, y, cell) in content {
+            if let Some(screen_cell) = shared.screen.cell_mut(Position::new(x, y)) {
+                *screen_cell = cell.clone();
+            }
+            cells.push(CellUpdate::new(x, y, cell));
+        }
+        shared.broadcast(ServerMessage::Draw(cells));
+        Ok(())
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        let mut shared = lock(&self.shared);
+        shared.cursor_visible = false;
+        shared.broadcast(ServerMessage::HideCursor);
+        Ok(())
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        let mut shared = lock(&self.shared);
+        shared.cursor_visible = true;
+        shared.broadcast(ServerMessage::ShowCursor);
+        Ok(())
+    }
+
+    fn get_cursor_position(&mut self) -> io::Result<Position> {
+        Ok(lock(&self.shared).cursor)
+    }
+
+    fn set_cursor_position<P: Into<Position>>(&mut self, position: P) -> io::Result<()> {
+        let position = position.into();
+        let mut shared = lock(&self.shared);
+        shared.cursor = position;
+        shared.broadcast(ServerMessage::SetCursor(position.x, position.y));
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        let mut shared = lock(&self.shared);
+        shared.screen.reset();
+        shared.broadcast(ServerMessage::Clear);
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        Ok(lock(&self.shared).size)
+    }
+
+    fn window_size(&mut self) -> io::Result<WindowSize> {
+        Ok(WindowSize {
+            columns_rows: lock(&self.shared).size,
+            pixels: Size::default(),
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl TuiBackend for SessionBackend {
+    fn enter(&mut self, features: Features) -> Result<()> {
+        self.set_mouse(features.mouse)
+    }
+
+    fn exit(&mut self, _features: Features) -> Result<()> {
+        let mut shared = lock(&self.shared);
+        shared.broadcast(ServerMessage::Detach);
+        shared.clients.clear();
+        Ok(())
+    }
+
+    fn set_mouse(&mut self, mouse: bool) -> Result<()> {
+        let mut shared = lock(&self.shared);
+        shared.mouse = mouse;
+        shared.broadcast(ServerMessage::Mouse(mouse));
+        Ok(())
+    }
+
+    /// Full colour, which each client downgrades to what its terminal supports.
+    fn capabilities(&mut self) -> Result<Capabilities> {
+        Ok(Capabilities {
+            colors: ColorSupport::TrueColor,
+            ..Capabilities::default()
+        })
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        let mut shared = lock(&self.shared);
+        shared.cursor_shape = shape;
+        shared.broadcast(ServerMessage::SetCursorShape(shape));
+        Ok(())
+    }
+
+    fn write_escape(&mut self, sequence: &str) -> Result<()> {
+        lock(&self.shared).broadcast(ServerMessage::Escape(sequence.to_string()));
+        Ok(())
+    }
+
+    /// The server has no terminal to give back to a shell, and exiting the terminal already
+    /// detached the clients.
+    fn suspend(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SessionBackend {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+/// The input events of all attached clients.
+#[derive(Clone, Debug)]
+pub struct SessionEvents {
+    events: Arc<tokio::sync::Mutex<Receiver<Event>>>,
+}
+
+impl EventSource for SessionEvents {
+    type Events = BoxStream<'static, Event>;
+
+    fn events(&mut self) -> Self::Events {
+        stream::unfold(self.events.clone(), |events| async move {
+            let event = events.lock().await.recv().await?;
+            Some((event, events))
+        })
+        .boxed()
+    }
+}
+
+/// Listen for clients on the Unix socket at `socket`, returning a [`Tui`] that draws to them and
+/// reads their input. A socket left behind by a server that is no longer running is replaced.
+///
+/// The socket can only be used by the current user, and clients of other users are rejected.
+pub fn listen(socket: &Path) -> Result<Tui<SessionBackend, SessionEvents>> {
+    if socket.exists() {
+        if std::os::unix::net::UnixStream::connect(socket).is_ok() {
+            return Err(eyre!(
+                "A session is already running at {}",
+                socket.display()
+            ));
+        }
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)
+        .map_err(|err| eyre!("Unable to listen on {}: {err}", socket.display()))?;
+    fs::set_permissions(socket, Permissions::from_mode(0o600))?;
+    info!("Listening for clients on {}", socket.display());
+    let shared = Arc::new(Mutex::new(Shared::new()));
+    // clients stop being read while the app is behind, instead of queueing their input
+    let (event_tx, event_rx) = mpsc::channel(EVENT_CAPACITY);
+    let task = tokio::spawn(accept(listener, shared.clone(), event_tx));
+    let backend = SessionBackend {
+        shared,
+        socket: socket.to_path_buf(),
+        task,
+    };
+    let input = SessionEvents {
+        events: Arc::new(tokio::sync::Mutex::new(event_rx)),
+    };
+    Tui::with_backend(backend, input, ViewportMode::Fullscreen)
+}
+
+async fn accept(listener: UnixListener, shared: Arc<Mutex<Shared>>, event_tx: Sender<Event>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => match stream.peer_cred() {
+                // SAFETY: geteuid has no preconditions and can't fail
+                Ok(peer) if peer.uid() == unsafe { libc::geteuid() } => {
+                    tokio::spawn(serve_client(stream, shared.clone(), event_tx.clone()));
+                }
+                Ok(peer) => warn!("Rejecting a client of user {}", peer.uid()),
+                Err(err) => warn!("Rejecting a client whose user is unknown: {err}"),
+            },
+            Err(err) => {
+                error!("Unable to accept clients: {err}");
+                break;
+            }
+        }
+    }
+}
+
+/// The longest line a client may send, which leaves plenty of room for a large paste.
+const MAX_LINE_LENGTH: u64 = 1 << 20;
+
+/// Whether `event` is input from the terminal, which is all a client may send.
+fn is_input(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Key(_)
+            | Event::Mouse(_)
+            | Event::Paste(_)
+            | Event::Resize(..)
+            | Event::FocusGained
+            | Event::FocusLost
+    )
+}
+
+/// Discard the rest of the current line.
+async fn skip_line(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<()> {
+    loop {
+        let buffer = reader.fill_buf().await?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        match buffer.iter().position(|&byte| byte == b'\n') {
+            Some(end) => {
+                reader.consume(end + 1);
+                return Ok(());
+            }
+            None => {
+                let length = buffer.len();
+                reader.consume(length);
+            }
+        }
+    }
+}
+
+/// Send the frames to a client and its input events to the app, until either side is done. Lines
+/// that are too long, aren't events or aren't input are skipped.
+async fn serve_client(stream: UnixStream, shared: Arc<Mutex<Shared>>, event_tx: Sender<Event>) {
+    info!("Client attached");
+    let (reader, mut writer) = stream.into_split();
+    let (message_tx, mut message_rx) = mpsc::unbounded_channel();
+    let id = lock(&shared).attach(message_tx);
+    let write = async {
+        while let Some(message) = message_rx.recv().await {
+            let detach = message == ServerMessage::Detach;
+            send(&mut writer, &message).await?;
+            if detach {
+                break;
+            }
+        }
+        Ok::<_, color_eyre::Report>(())
+    };
+    let read = async {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let limit = MAX_LINE_LENGTH + 1; // room for the newline
+            if (&mut reader)
+                .take(limit)
+                .read_until(b'\n', &mut line)
+                .await?
+                == 0
+            {
+                break;
+            }
+            if line.len() as u64 == limit && !line.ends_with(b"\n") {
+                warn!("Skipping a line longer than {MAX_LINE_LENGTH} bytes from a client");
+                skip_line(&mut reader).await?;
+                continue;
+            }
+            let mut event: Event = match serde_json::from_slice(&line) {
+                Ok(event) if is_input(&event) => event,
+                Ok(event) => {
+                    warn!("Skipping {event:?} from a client, which may only send input");
+                    continue;
+                }
+                Err(err) => {
+                    warn!("Skipping a line from a client that isn't an event: {err}");
+                    continue;
+                }
+            };
+            if let Event::Resize(width, height) = event {
+                let size = lock(&shared).resize_client(id, Size::new(width, height));
+                event = Event::Resize(size.width, size.height);
+            }
+            if event_tx.send(event).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    };
+    let result = tokio::select! {
+        result = write => result,
+        result = read => result,
+    };
+    if let Err(err) = result {
+        warn!("Lost the connection to a client: {err}");
+    }
+    let resized = lock(&shared).detach(id);
+    if let Some(size) = resized {
+        // the frame can grow now that a smaller client has gone
+        let _ = event_tx.send(Event::Resize(size.width, size.height)).await;
+    }
+    info!("Client detached");
+}
+
+/// Attach the terminal to the app served at `socket`, until `detach` is pressed or the app
+/// quits.
+pub async fn attach(socket: &Path, detach: KeyEvent) -> Result<()> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .map_err(|err| eyre!("Unable to attach to {}: {err}", socket.display()))?;
+    let mut tui = Tui::new()?.paste(true).focus_change(true);
+    tui.enter()?;
+    let result = forward(&mut tui, stream, detach).await;
+    tui.stop().await?;
+    tui.exit()?;
+    result
+}
+
+/// Send the input events of `tui` to the server and draw what the server sends back.
+async fn forward(tui: &mut Tui, stream: UnixStream, detach: KeyEvent) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let colors = tui.capabilities.unwrap_or_default().colors;
+    let size = tui.size()?;
+    send(&mut writer, &Event::Resize(size.width, size.height)).await?;
+    loop {
+        tokio::select! {
+            event = tui.next_event() => match event {
+                Some(Event::Key(key)) if key == detach => return Ok(()),
+                Some(Event::Quit) | None => return Ok(()),
+                Some(event) if is_input(&event) => send(&mut writer, &event).await?,
+                Some(_) => {} // the server has its own ticks and renders
+            },
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                match serde_json::from_str::<ServerMessage>(&line)? {
+                    ServerMessage::Mouse(mouse) => tui.set_mouse(mouse)?,
+                    ServerMessage::SetCursorShape(shape) => tui.set_cursor_shape(shape)?,
+                    ServerMessage::Escape(sequence) => {
+                        tui.backend_mut().write_escape(&sequence)?;
+                    }
+                    ServerMessage::Detach => return Ok(()),
+                    message => apply(tui.backend_mut(), &message, colors)?,
+                }
+            }
+        }
+    }
+}
+
+/// Draw a message from the server onto `backend`, whose terminal supports `colors`.
+fn apply<B: Backend>(backend: &mut B, message: &ServerMessage, colors: ColorSupport) -> Result<()> {
+    match message {
+        ServerMessage::Draw(updates) => {
+            let cells: Vec<_> = updates
+                .iter()
+                .map(|update| (update.x, update.y, update.to_cell(colors)))
+                .collect();
+            backend.draw(cells.iter().map(|(x, y, cell)| (*x, *y, cell)))?;
+        }
+        ServerMessage::Clear => backend.clear()?,
+        ServerMessage::HideCursor => backend.hide_cursor()?,
+        ServerMessage::ShowCursor => backend.show_cursor()?,
+        ServerMessage::SetCursor(x, y) => backend.set_cursor_position(Position::new(*x, *y))?,
+        ServerMessage::SetCursorShape(_)
+        | ServerMessage::Escape(_)
+        | ServerMessage::Mouse(_)
+        | ServerMessage::Detach => {}
+    }
+    backend.flush()?;
+    Ok(())
+}
+
+/// Write `message` as a line of JSON.
+async fn send<T: Serialize>(writer: &mut (impl AsyncWrite + Unpin), message: &T) -> Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, time::Duration};
+
+    use pretty_assertions::assert_eq;
+    use ratatui::{backend::TestBackend, style::Color, widgets::Paragraph};
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_client_receives_frames_at_its_size() -> Result<()> {
+        let socket = env::temp_dir().join(format!("session-test-{}.sock", std::process::id()));
+        let mut tui = listen(&socket)?;
+        let (reader, mut writer) = UnixStream::connect(&socket).await?.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        send(&mut writer, &Event::Resize(20, 5)).await?;
+        let event = timeout(Duration::from_secs(1), tui.input.events().next()).await?;
+        assert!(matches!(event, Some(Event::Resize(20, 5))));
+        assert_eq!(tui.size()?, Size::new(20, 5));
+
+        tui.draw(|frame| frame.render_widget(Paragraph::new("hello"), frame.area()))?;
+        let drawn = loop {
+            let line = timeout(Duration::from_secs(1), lines.next_line())
+                .await??
+                .ok_or_else(|| eyre!("The server closed the connection"))?;
+            if let ServerMessage::Draw(cells) = serde_json::from_str(&line)? {
+                if cells.first().is_some_and(|cell| cell.symbol == "h") {
+                    break cells;
+                }
+            }
+        };
+        let text: String = drawn.iter().map(|cell| cell.symbol.as_str()).collect();
+        assert_eq!(text, "hello");
+        assert_eq!((drawn[4].x, drawn[4].y), (4, 0));
+
+        drop(tui);
+        assert!(!socket.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_socket_is_private() -> Result<()> {
+        let socket = env::temp_dir().join(format!("session-mode-{}.sock", std::process::id()));
+        let tui = listen(&socket)?;
+        let mode = fs::metadata(&socket)?.permissions().mode();
+        drop(tui);
+        assert_eq!(mode & 0o777, 0o600);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_lines_that_are_not_input_are_skipped() -> Result<()> {
+        let socket = env::temp_dir().join(format!("session-skip-{}.sock", std::process::id()));
+        let mut tui = listen(&socket)?;
+        let (_reader, mut writer) = UnixStream::connect(&socket).await?.into_split();
+
+        writer.write_all(b"not json\n").await?;
+        send(&mut writer, &Event::Quit).await?;
+        let long = vec![b'x'; MAX_LINE_LENGTH as usize + 10];
+        writer.write_all(&long).await?;
+        writer.write_all(b"\n").await?;
+        send(&mut writer, &Event::FocusGained).await?;
+        let event = timeout(Duration::from_secs(1), tui.input.events().next()).await?;
+        assert!(matches!(event, Some(Event::FocusGained)));
+
+        drop(tui);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_draws_cells() -> Result<()> {
+        let mut backend = TestBackend::new(5, 1);
+        let style = Style::new().fg(Color::Red);
+        let update = CellUpdate {
+            x: 1,
+            y: 0,
+            symbol: "a".to_string(),
+            style,
+        };
+        apply(
+            &mut backend,
+            &ServerMessage::Draw(vec![update]),
+            ColorSupport::TrueColor,
+        )?;
+        let mut expected = Buffer::with_lines([" a   "]);
+        expected.set_style(Rect::new(1, 0, 1, 1), style);
+        backend.assert_buffer(&expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_downgrades_colors() -> Result<()> {
+        let mut backend = TestBackend::new(1, 1);
+        let update = CellUpdate {
+            x: 0,
+            y: 0,
+            symbol: "a".to_string(),
+            style: Style::new().fg(Color::Rgb(255, 0, 0)),
+        };
+        apply(
+            &mut backend,
+            &ServerMessage::Draw(vec![update]),
+            ColorSupport::Ansi16,
+        )?;
+        let mut expected = Buffer::with_lines(["a"]);
+        expected.set_style(
+            Rect::new(0, 0, 1, 1),
+            ColorSupport::Ansi16.downgrade_style(Style::new().fg(Color::Rgb(255, 0, 0))),
+        );
+        backend.assert_buffer(&expected);
+        assert_ne!(backend.buffer()[(0, 0)].fg, Color::Rgb(255, 0, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_frames_fit_the_smallest_client() {
+        let mut shared = Shared::new();
+        let (first_tx, _first_rx) = mpsc::unbounded_channel();
+        let (second_tx, _second_rx) = mpsc::unbounded_channel();
+        let first = shared.attach(first_tx);
+        let second = shared.attach(second_tx);
+
+        assert_eq!(
+            shared.resize_client(first, Size::new(100, 20)),
+            Size::new(100, 20)
+        );
+        assert_eq!(
+            shared.resize_client(second, Size::new(80, 30)),
+            Size::new(80, 20)
+        );
+        assert_eq!(shared.screen.area, Rect::new(0, 0, 80, 20));
+        assert_eq!(shared.detach(second), Some(Size::new(100, 20)));
+        assert_eq!(shared.detach(first), None);
+        assert_eq!(shared.size, Size::new(100, 20));
+    }
+}